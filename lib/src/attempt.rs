@@ -0,0 +1,235 @@
+use std::convert::Infallible;
+
+use jiff::{SignedDuration, Timestamp};
+
+use crate::{
+    Delta, Sample, Settings, SyncError, SyncRecord, SyncReport, history, holdover,
+    sampling::Samples,
+};
+
+/// What to store at the end of a sync attempt, and its result.
+///
+/// Store the history first, then the offset, then the record.
+#[derive(Debug)]
+pub(crate) struct Conclusion<E> {
+    /// The new sample history, if one is kept.
+    pub(crate) history: Option<Vec<Sample>>,
+
+    /// The new offset, if the attempt succeeded.
+    pub(crate) offset: Option<SignedDuration>,
+
+    /// The new sync record, if there's one.
+    pub(crate) record: Option<SyncRecord>,
+
+    /// The result of the attempt.
+    pub(crate) result: Result<SyncReport, SyncError<E>>,
+}
+
+/// Conclude a sync attempt from its round of samples.
+///
+/// This is the part of the attempt after sampling that's the same whatever the runtime: check
+/// the round, slide it into the sample `history` if one is kept, estimate the offset, check it
+/// against the limits, and update the sync `record` for the success or the failure.
+///
+/// The `history` is that loaded from storage, or nothing if it's not kept.
+pub(crate) fn conclude<E>(
+    mut round: Samples<E>,
+    settings: Settings,
+    current_offset: Option<SignedDuration>,
+    history: Vec<Sample>,
+    record: Option<SyncRecord>,
+    now: Timestamp,
+) -> Conclusion<E> {
+    let settings = settings.clamp();
+    let failed = |err, history| Conclusion {
+        history,
+        offset: None,
+        record: holdover::failed(record),
+        result: Err(err),
+    };
+
+    if let Err(err) = round.check() {
+        return failed(err, None);
+    }
+
+    let (deltas, history) = match settings.history {
+        Some(keep) if !round.deltas.is_empty() => {
+            let window = history::slide(
+                history,
+                round.deltas.iter().copied().map(Sample::from),
+                keep,
+                now,
+            );
+            tracing::trace!(count = window.len(), "storing sample history");
+            let deltas = window.iter().copied().map(Delta::from).collect();
+            (deltas, Some(window))
+        }
+        _ => (round.deltas.clone(), None),
+    };
+
+    let (estimate, report) = match round.conclude(deltas, settings.outliers) {
+        Ok(concluded) => concluded,
+        Err(err) => return failed(err, history),
+    };
+
+    if let Err(err) = settings.check_offset(current_offset, estimate.offset) {
+        return failed(err, history);
+    }
+
+    tracing::debug!(offset=?estimate.offset, "storing calculated offset");
+    let record = SyncRecord::after(record, now, estimate.offset, estimate.error());
+    tracing::trace!(?record, "storing sync record");
+    Conclusion {
+        history,
+        offset: Some(estimate.offset),
+        record: Some(record),
+        result: Ok(report),
+    }
+}
+
+/// The initial delta to store as the offset, if none is stored yet and it's within the limits.
+pub(crate) fn initial_offset(
+    settings: &Settings,
+    current_offset: Option<SignedDuration>,
+    packet: Delta,
+) -> Option<SignedDuration> {
+    if current_offset.is_some() {
+        return None;
+    }
+
+    if let Err(err) = settings.check_offset::<Infallible>(None, packet.delta) {
+        tracing::debug!(%err, "initial delta is outside limits, not storing");
+        return None;
+    }
+
+    tracing::debug!(offset=?packet.delta, "no offset stored, storing initial delta");
+    Some(packet.delta)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::History;
+
+    fn now() -> Timestamp {
+        Timestamp::from_second(1_700_000_000).unwrap()
+    }
+
+    fn round(deltas_ms: &[i64]) -> Samples {
+        let mut round = Samples::with_capacity(deltas_ms.len() as u8);
+        round.deltas = deltas_ms
+            .iter()
+            .map(|ms| {
+                Delta::from(Sample {
+                    at: now(),
+                    latency: Duration::from_millis(10),
+                    delta: SignedDuration::from_millis(*ms),
+                })
+            })
+            .collect();
+        round
+    }
+
+    fn record() -> SyncRecord {
+        SyncRecord {
+            at: now() - SignedDuration::from_hours(1),
+            error: Duration::from_millis(10),
+            offset: SignedDuration::ZERO,
+            drift: None,
+            failed_attempts: 0,
+        }
+    }
+
+    #[test]
+    fn success() {
+        let conclusion = conclude(
+            round(&[100, 100, 100]),
+            Settings::default(),
+            None,
+            Vec::new(),
+            Some(record()),
+            now(),
+        );
+        assert_eq!(conclusion.offset, Some(SignedDuration::from_millis(100)));
+        assert_eq!(conclusion.history, None);
+        let record = conclusion.record.unwrap();
+        assert_eq!(record.at, now());
+        assert_eq!(record.offset, SignedDuration::from_millis(100));
+        assert_eq!(conclusion.result.unwrap().samples, 3);
+    }
+
+    #[test]
+    fn failure_counts_in_record() {
+        let mut failed = round(&[]);
+        failed.failures = 3;
+        let conclusion = conclude(
+            failed,
+            Settings::default(),
+            None,
+            Vec::new(),
+            Some(record()),
+            now(),
+        );
+        assert!(matches!(
+            conclusion.result,
+            Err(SyncError::AllQueriesFailed { .. })
+        ));
+        assert_eq!(conclusion.offset, None);
+        assert_eq!(conclusion.record.unwrap().failed_attempts, 1);
+
+        let conclusion = conclude(
+            round(&[100]),
+            Settings::default(),
+            None,
+            Vec::new(),
+            None,
+            now(),
+        );
+        assert!(matches!(
+            conclusion.result,
+            Err(SyncError::TooFewInliers { samples: 1, .. })
+        ));
+        assert_eq!(conclusion.record, None);
+    }
+
+    #[test]
+    fn history_completes_the_round() {
+        let previous = round(&[100, 100])
+            .deltas
+            .into_iter()
+            .map(Sample::from)
+            .collect();
+        let conclusion = conclude(
+            round(&[100]),
+            Settings {
+                history: Some(History {
+                    max_samples: 10,
+                    window: Duration::from_secs(60),
+                }),
+                ..Default::default()
+            },
+            None,
+            previous,
+            None,
+            now(),
+        );
+        assert_eq!(conclusion.history.map(|history| history.len()), Some(3));
+        assert_eq!(conclusion.offset, Some(SignedDuration::from_millis(100)));
+    }
+
+    #[test]
+    fn initial_offset_only_when_none() {
+        let packet = round(&[100]).deltas[0];
+        let settings = Settings::default();
+        assert_eq!(
+            initial_offset(&settings, None, packet),
+            Some(SignedDuration::from_millis(100))
+        );
+        assert_eq!(
+            initial_offset(&settings, Some(SignedDuration::ZERO), packet),
+            None
+        );
+    }
+}
@@ -0,0 +1,255 @@
+//! Blocking client and server, for applications without an async runtime.
+//!
+//! These traits mirror the async [`TimeSource`](crate::TimeSource),
+//! [`TimesimpServer`](crate::TimesimpServer), and [`TimesimpClient`](crate::TimesimpClient), with
+//! plain functions instead of async ones. The sync is driven by a [`SyncSession`], so the
+//! sampling and statistics are the same as with the async traits.
+//!
+//! As queries block, they're made one after the other even if [`Settings.in_flight`](Settings)
+//! is more than one, and they can't be abandoned: a query that takes longer than the timeout is
+//! counted as timed out once it returns, but the sync still waits for it. Enforce a timeout in
+//! `query_server()` itself, with your transport's read timeout or equivalent, to not wait.
+//!
+//! This module is only available with the `blocking` feature.
+
+use std::time::Duration;
+
+use jiff::{SignedDuration, Timestamp};
+
+use crate::{
+    Action, Clock, Request, Response, Sample, Settings, SyncError, SyncRecord, SyncReport,
+    SyncSession, SyncState, SystemClock, TimeBounds, attempt,
+};
+
+/// A source of adjusted time, blocking.
+///
+/// This is the blocking equivalent of [`crate::TimeSource`]. You must implement the required
+/// function and not override the others, except for the optional hooks.
+pub trait TimeSource {
+    /// Error for your required methods.
+    type Err: std::error::Error;
+
+    /// Load the current time offset.
+    ///
+    /// This must return the current stored time offset, or `None` if no time offset is currently
+    /// stored.
+    fn load_offset(&self) -> Result<Option<SignedDuration>, Self::Err>;
+
+    /// The clock to read the time from.
+    ///
+    /// Optional: by default this is the [`SystemClock`].
+    fn clock(&self) -> &dyn Clock {
+        &SystemClock
+    }
+
+    /// Load the record of the last successful sync.
+    ///
+    /// Optional: by default this returns `None`. This must return the last record given to
+    /// `store_sync_record()`, or `None` if there's none.
+    fn load_sync_record(&self) -> Result<Option<SyncRecord>, Self::Err> {
+        Ok(None)
+    }
+
+    /// Obtain an adjusted timestamp.
+    ///
+    /// Do not override.
+    fn adjusted_timestamp(&self) -> Result<Timestamp, Self::Err> {
+        let offset = self.load_offset()?.unwrap_or_default();
+        Ok(self.clock().now() + offset)
+    }
+
+    /// Obtain an adjusted timestamp, extrapolated with the learned drift.
+    ///
+    /// Do not override.
+    ///
+    /// See [`crate::TimeSource::extrapolated_timestamp()`].
+    fn extrapolated_timestamp(&self) -> Result<Timestamp, Self::Err> {
+        let Some(record) = self.load_sync_record()? else {
+            return self.adjusted_timestamp();
+        };
+
+        let now = self.clock().now();
+        Ok(now + record.offset_at(now))
+    }
+
+    /// Obtain the adjusted time as an interval.
+    ///
+    /// Do not override.
+    ///
+    /// See [`crate::TimeSource::adjusted_bounds()`].
+    fn adjusted_bounds(&self, drift: u32) -> Result<Option<TimeBounds>, Self::Err> {
+        let Some(record) = self.load_sync_record()? else {
+            return Ok(None);
+        };
+
+        let now = self.clock().now();
+        Ok(Some(TimeBounds::new(
+            now + record.offset_at(now),
+            record.error_at(now, drift),
+        )))
+    }
+
+    /// Obtain the state of synchronisation.
+    ///
+    /// Do not override.
+    ///
+    /// See [`crate::TimeSource::sync_state()`].
+    fn sync_state(&self, drift: u32) -> Result<Option<SyncState>, Self::Err> {
+        let Some(record) = self.load_sync_record()? else {
+            return Ok(None);
+        };
+
+        Ok(Some(SyncState::from_record(
+            record,
+            self.clock().now(),
+            drift,
+        )))
+    }
+}
+
+/// A time sync server, blocking.
+///
+/// There's nothing to implement beyond the [`TimeSource`]: implement this with an empty `impl`
+/// block, then use `answer_client()` to implement your server endpoint.
+pub trait TimesimpServer: TimeSource {
+    /// The implementation of the server endpoint.
+    ///
+    /// Do not override.
+    fn answer_client(&self, request: Request) -> Result<Response, Self::Err> {
+        Ok(Response {
+            client: request.client,
+            server: self.adjusted_timestamp()?,
+        })
+    }
+}
+
+/// A time sync client, blocking.
+///
+/// This is the blocking equivalent of [`crate::TimesimpClient`]. You must implement the two
+/// required functions and not override the others, except for `sleep()` and the optional
+/// storage hooks.
+pub trait TimesimpClient: TimeSource {
+    /// Store the current time offset.
+    ///
+    /// Once `store_offset` has been called once, `load_offset` should return `Some`.
+    fn store_offset(&mut self, offset: SignedDuration) -> Result<(), Self::Err>;
+
+    /// Query a timesimp server endpoint.
+    ///
+    /// This must send the given [`Request`] to a timesimp server and obtain a [`Response`], doing
+    /// as little else as possible to avoid adding unnecessary latency.
+    fn query_server(&self, request: Request) -> Result<Response, Self::Err>;
+
+    /// Sleep for a [`Duration`].
+    ///
+    /// By default this is [`std::thread::sleep`].
+    fn sleep(&self, duration: Duration) {
+        std::thread::sleep(duration);
+    }
+
+    /// Store the record of the last successful sync.
+    ///
+    /// Optional: by default this does nothing.
+    fn store_sync_record(&mut self, record: SyncRecord) -> Result<(), Self::Err> {
+        let _ = record;
+        Ok(())
+    }
+
+    /// Store the sample history.
+    ///
+    /// Optional: by default this does nothing. The samples given replace any previously stored.
+    fn store_samples(&mut self, samples: Vec<Sample>) -> Result<(), Self::Err> {
+        let _ = samples;
+        Ok(())
+    }
+
+    /// Load the sample history.
+    ///
+    /// Optional: by default this returns nothing.
+    fn load_samples(&self) -> Result<Vec<Sample>, Self::Err> {
+        Ok(Vec::new())
+    }
+
+    /// The main client state driver. Call this in a loop.
+    ///
+    /// Do not override.
+    ///
+    /// See [`crate::TimesimpClient::attempt_sync()`].
+    fn attempt_sync(&mut self, settings: Settings) -> Result<SignedDuration, SyncError<Self::Err>> {
+        self.attempt_sync_report(settings)
+            .map(|report| report.offset)
+    }
+
+    /// The main client state driver, with a report of the attempt.
+    ///
+    /// Do not override.
+    ///
+    /// See [`crate::TimesimpClient::attempt_sync_report()`].
+    fn attempt_sync_report(
+        &mut self,
+        settings: Settings,
+    ) -> Result<SyncReport, SyncError<Self::Err>> {
+        let current_offset = self.load_offset().map_err(SyncError::Storage)?;
+        tracing::trace!(?settings, ?current_offset, "starting delta collection");
+
+        let mut session = SyncSession::new(settings, self.clock());
+        let mut errors = Vec::new();
+        let mut initial_stored = false;
+        loop {
+            match session.poll(self.clock()) {
+                Action::Send(id, request) => {
+                    match self.query_server(request) {
+                        Ok(response) => session.receive(id, response, self.clock().instant()),
+                        Err(err) => {
+                            tracing::error!(?err, "query_server failed");
+                            session.fail(id, self.clock().instant());
+                            errors.push(err);
+                        }
+                    }
+
+                    if !initial_stored && let Some(packet) = session.round().deltas.first() {
+                        initial_stored = true;
+                        let current = self.load_offset().map_err(SyncError::Storage)?;
+                        if let Some(offset) = attempt::initial_offset(&settings, current, *packet) {
+                            self.store_offset(offset).map_err(SyncError::Storage)?;
+                        }
+                    }
+                }
+                Action::Wait(until) => {
+                    // queries complete before the next poll, so there's always an end to the wait
+                    let wait = until.map_or(Duration::ZERO, |until| {
+                        until.saturating_duration_since(self.clock().instant())
+                    });
+                    self.sleep(wait);
+                }
+                Action::Done(_) => break,
+            }
+        }
+
+        let history = match settings.history {
+            Some(_) => self.load_samples().map_err(SyncError::Storage)?,
+            None => Vec::new(),
+        };
+        let record = self.load_sync_record().map_err(SyncError::Storage)?;
+        let conclusion = attempt::conclude(
+            session.into_round().with_errors(errors),
+            settings,
+            current_offset,
+            history,
+            record,
+            self.clock().now(),
+        );
+
+        if let Some(history) = conclusion.history {
+            self.store_samples(history).map_err(SyncError::Storage)?;
+        }
+        if let Some(offset) = conclusion.offset {
+            self.store_offset(offset).map_err(SyncError::Storage)?;
+        }
+        if let Some(record) = conclusion.record {
+            self.store_sync_record(record).map_err(SyncError::Storage)?;
+        }
+
+        conclusion.result
+    }
+}
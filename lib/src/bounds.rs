@@ -0,0 +1,259 @@
+use std::time::Duration;
+
+use jiff::{SignedDuration, Timestamp};
+
+/// What's known about the accuracy of the last sync.
+///
+/// This is stored with
+/// [`TimesimpClient::store_sync_record()`](crate::TimesimpClient::store_sync_record) after every
+/// successful sync, and used to compute [`TimeBounds`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SyncRecord {
+    /// When the sync finished, on the local (unadjusted) system clock.
+    pub at: Timestamp,
+
+    /// The maximum error of the offset obtained by the sync.
+    ///
+    /// This is the lowest one-way latency seen (half the round trip) plus the dispersion of the
+    /// samples kept.
+    pub error: Duration,
+
+    /// The offset obtained by the sync.
+    pub offset: SignedDuration,
+
+    /// The drift of the local clock relative to the server's, in parts per billion.
+    ///
+    /// This is learned from the change in offset between two syncs; see
+    /// [`SyncState`](crate::SyncState). Positive if the offset is growing, that is if the local
+    /// clock is slower than the server's.
+    pub drift: Option<i64>,
+
+    /// How many sync attempts failed since this sync.
+    pub failed_attempts: u32,
+}
+
+impl SyncRecord {
+    /// The record of a successful sync, learning the drift from the previous record.
+    ///
+    /// The drift is only learned if the syncs are at least a minute apart, as the offsets' errors
+    /// would dominate otherwise; and if it's less than 500ppm, as a larger change is a step, not
+    /// drift. Otherwise the previous drift is kept.
+    pub(crate) fn after(
+        previous: Option<SyncRecord>,
+        at: Timestamp,
+        offset: SignedDuration,
+        error: Duration,
+    ) -> Self {
+        let drift = previous.and_then(|previous| {
+            let elapsed = at.duration_since(previous.at);
+            if elapsed < SignedDuration::from_mins(1) {
+                return previous.drift;
+            }
+
+            let change = offset - previous.offset;
+            let drift = change.as_nanos() * 1_000_000_000 / elapsed.as_nanos();
+            if drift.abs() > 500_000 {
+                tracing::debug!(?change, ?elapsed, "offset change is too large for drift");
+                return previous.drift;
+            }
+
+            // UNWRAP: just checked it's small
+            Some(i64::try_from(drift).unwrap())
+        });
+
+        Self {
+            at,
+            error,
+            offset,
+            drift,
+            failed_attempts: 0,
+        }
+    }
+
+    /// The offset extrapolated with the learned drift, some time after the sync.
+    ///
+    /// If no drift was learned, this is the offset.
+    pub fn offset_at(&self, now: Timestamp) -> SignedDuration {
+        let Some(drift) = self.drift else {
+            return self.offset;
+        };
+
+        let elapsed = now.duration_since(self.at).as_nanos();
+        let change = elapsed * i128::from(drift) / 1_000_000_000;
+        self.offset + SignedDuration::from_nanos(change.try_into().unwrap_or(i64::MAX))
+    }
+
+    /// The maximum error of the offset, some time after the sync.
+    ///
+    /// The local clock drifts from the server's by up to `drift` parts per billion, the same unit
+    /// as the learned [`drift`](SyncRecord::drift), so the error grows by that much of the time
+    /// elapsed since the sync. Typical clocks drift by less than 100ppm, that is `100_000`; NTP
+    /// assumes at most 500ppm. The drift is capped at 50%, that is `500_000_000`.
+    pub fn error_at(&self, now: Timestamp, drift: u32) -> Duration {
+        let elapsed = now.duration_since(self.at).unsigned_abs();
+        let widening = elapsed.as_nanos() * u128::from(drift.min(500_000_000)) / 1_000_000_000;
+        self.error.saturating_add(Duration::from_nanos(
+            widening.try_into().unwrap_or(u64::MAX),
+        ))
+    }
+}
+
+/// An interval the true time is within.
+///
+/// Obtained from [`TimeSource::adjusted_bounds()`](crate::TimeSource::adjusted_bounds).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct TimeBounds {
+    /// The earliest the true time may be.
+    pub earliest: Timestamp,
+
+    /// The latest the true time may be.
+    pub latest: Timestamp,
+}
+
+impl TimeBounds {
+    /// An interval of the given error on either side of a timestamp.
+    pub fn new(timestamp: Timestamp, error: Duration) -> Self {
+        let error = SignedDuration::try_from(error).unwrap_or(SignedDuration::MAX);
+        // UNWRAP: saturating arithmetic only fails for spans with calendar units
+        Self {
+            earliest: timestamp.saturating_sub(error).unwrap(),
+            latest: timestamp.saturating_add(error).unwrap(),
+        }
+    }
+
+    /// The middle of the interval.
+    pub fn midpoint(&self) -> Timestamp {
+        self.earliest + self.latest.duration_since(self.earliest) / 2
+    }
+
+    /// How wide the interval is.
+    pub fn width(&self) -> Duration {
+        self.latest.duration_since(self.earliest).unsigned_abs()
+    }
+
+    /// Whether the true time is definitely after the given timestamp.
+    pub fn is_after(&self, timestamp: Timestamp) -> bool {
+        self.earliest > timestamp
+    }
+
+    /// Whether the true time is definitely before the given timestamp.
+    pub fn is_before(&self, timestamp: Timestamp) -> bool {
+        self.latest < timestamp
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ts(secs: i64) -> Timestamp {
+        Timestamp::from_second(secs).unwrap()
+    }
+
+    fn record(at: i64, offset_ms: i64, drift: Option<i64>) -> SyncRecord {
+        SyncRecord {
+            at: ts(at),
+            error: Duration::from_millis(3),
+            offset: SignedDuration::from_millis(offset_ms),
+            drift,
+            failed_attempts: 0,
+        }
+    }
+
+    #[test]
+    fn no_drift() {
+        let record = record(1000, 0, None);
+        assert_eq!(record.error_at(ts(1000), 0), Duration::from_millis(3));
+        assert_eq!(record.error_at(ts(5000), 0), Duration::from_millis(3));
+    }
+
+    #[test]
+    fn widens_with_drift() {
+        let record = record(1000, 0, None);
+        // 100 ppm over 1000 seconds is 100ms
+        assert_eq!(
+            record.error_at(ts(2000), 100_000),
+            Duration::from_millis(103)
+        );
+    }
+
+    #[test]
+    fn widens_when_clock_went_backwards() {
+        let record = record(1000, 0, None);
+        assert_eq!(record.error_at(ts(0), 100_000), Duration::from_millis(103));
+    }
+
+    #[test]
+    fn learns_drift() {
+        let first = SyncRecord::after(
+            None,
+            ts(1000),
+            SignedDuration::from_millis(10),
+            Duration::ZERO,
+        );
+        assert_eq!(first.drift, None);
+
+        // 1ms over 100 seconds is 10ppm
+        let second = SyncRecord::after(
+            Some(first),
+            ts(1100),
+            SignedDuration::from_millis(11),
+            Duration::ZERO,
+        );
+        assert_eq!(second.drift, Some(10_000));
+    }
+
+    #[test]
+    fn keeps_drift_when_too_close() {
+        let previous = record(1000, 10, Some(10_000));
+        let next = SyncRecord::after(
+            Some(previous),
+            ts(1030),
+            SignedDuration::from_millis(20),
+            Duration::ZERO,
+        );
+        assert_eq!(next.drift, Some(10_000));
+    }
+
+    #[test]
+    fn step_is_not_drift() {
+        let previous = record(1000, 10, Some(10_000));
+        let next = SyncRecord::after(
+            Some(previous),
+            ts(1100),
+            SignedDuration::from_secs(5),
+            Duration::ZERO,
+        );
+        assert_eq!(next.drift, Some(10_000));
+    }
+
+    #[test]
+    fn extrapolates() {
+        assert_eq!(
+            record(1000, 10, None).offset_at(ts(2000)),
+            SignedDuration::from_millis(10)
+        );
+        assert_eq!(
+            record(1000, 10, Some(10_000)).offset_at(ts(2000)),
+            SignedDuration::from_millis(20)
+        );
+        assert_eq!(
+            record(1000, 10, Some(-10_000)).offset_at(ts(2000)),
+            SignedDuration::ZERO
+        );
+    }
+
+    #[test]
+    fn interval() {
+        let bounds = TimeBounds::new(ts(1000), Duration::from_secs(2));
+        assert_eq!(bounds.earliest, ts(998));
+        assert_eq!(bounds.latest, ts(1002));
+        assert_eq!(bounds.midpoint(), ts(1000));
+        assert_eq!(bounds.width(), Duration::from_secs(4));
+
+        assert!(bounds.is_after(ts(997)));
+        assert!(!bounds.is_after(ts(999)));
+        assert!(bounds.is_before(ts(1003)));
+        assert!(!bounds.is_before(ts(1001)));
+    }
+}
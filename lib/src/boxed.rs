@@ -0,0 +1,168 @@
+use std::{pin::Pin, time::Duration};
+
+use jiff::SignedDuration;
+
+use crate::{Clock, Request, Response, Sample, SyncRecord, TimeSource, TimesimpClient};
+
+/// A boxed future, as returned by [`DynTimesimpClient`].
+pub type BoxFuture<'a, T> = Pin<Box<dyn Future<Output = T> + 'a>>;
+
+/// A time sync client that can be made into a trait object.
+///
+/// [`TimesimpClient`] has async methods, so it can't be used as `dyn TimesimpClient`. This is the
+/// same, with the methods suffixed with `_dyn` and returning boxed futures, so they don't clash
+/// with those of the async traits. It's implemented for every `TimesimpClient`, so you don't
+/// implement it yourself, and a `Box<dyn DynTimesimpClient>` is itself a `TimesimpClient`. This
+/// lets you keep different kinds of clients together, and pick one at runtime:
+///
+/// ```no_run
+/// use timesimp::{DynTimesimpClient, Settings, TimesimpClient};
+///
+/// async fn sync_all<E: std::error::Error>(clients: &mut [Box<dyn DynTimesimpClient<Err = E>>]) {
+///     for client in clients {
+///         if let Err(err) = client.attempt_sync(Settings::default()).await {
+///             eprintln!("{err}");
+///         }
+///     }
+/// }
+/// ```
+///
+/// All the clients must have the same [`Err`](TimeSource::Err) type, so you may need to convert
+/// their errors into a common type, such as an enum of them.
+///
+/// The futures are not `Send`: see [`SendTimesimpClient`](crate::SendTimesimpClient) for that.
+pub trait DynTimesimpClient {
+    /// Error for the client methods.
+    type Err: std::error::Error;
+
+    /// Load the current time offset.
+    ///
+    /// See [`TimeSource::load_offset()`].
+    fn load_offset_dyn(&self) -> BoxFuture<'_, Result<Option<SignedDuration>, Self::Err>>;
+
+    /// The clock to read the time from.
+    ///
+    /// See [`TimeSource::clock()`].
+    fn clock_dyn(&self) -> &dyn Clock;
+
+    /// Load the record of the last successful sync.
+    ///
+    /// See [`TimeSource::load_sync_record()`].
+    fn load_sync_record_dyn(&self) -> BoxFuture<'_, Result<Option<SyncRecord>, Self::Err>>;
+
+    /// Store the current time offset.
+    ///
+    /// See [`TimesimpClient::store_offset()`].
+    fn store_offset_dyn(&mut self, offset: SignedDuration) -> BoxFuture<'_, Result<(), Self::Err>>;
+
+    /// Query a timesimp server endpoint.
+    ///
+    /// See [`TimesimpClient::query_server()`].
+    fn query_server_dyn(&self, request: Request) -> BoxFuture<'_, Result<Response, Self::Err>>;
+
+    /// Sleep for a [`Duration`].
+    ///
+    /// See [`TimesimpClient::sleep()`].
+    fn sleep_dyn(&self, duration: Duration) -> BoxFuture<'_, ()>;
+
+    /// Store the record of the last successful sync.
+    ///
+    /// See [`TimesimpClient::store_sync_record()`].
+    fn store_sync_record_dyn(&mut self, record: SyncRecord)
+    -> BoxFuture<'_, Result<(), Self::Err>>;
+
+    /// Store the sample history.
+    ///
+    /// See [`TimesimpClient::store_samples()`].
+    fn store_samples_dyn(&mut self, samples: Vec<Sample>) -> BoxFuture<'_, Result<(), Self::Err>>;
+
+    /// Load the sample history.
+    ///
+    /// See [`TimesimpClient::load_samples()`].
+    fn load_samples_dyn(&self) -> BoxFuture<'_, Result<Vec<Sample>, Self::Err>>;
+}
+
+impl<T: TimesimpClient> DynTimesimpClient for T {
+    type Err = T::Err;
+
+    fn load_offset_dyn(&self) -> BoxFuture<'_, Result<Option<SignedDuration>, Self::Err>> {
+        Box::pin(TimeSource::load_offset(self))
+    }
+
+    fn clock_dyn(&self) -> &dyn Clock {
+        TimeSource::clock(self)
+    }
+
+    fn load_sync_record_dyn(&self) -> BoxFuture<'_, Result<Option<SyncRecord>, Self::Err>> {
+        Box::pin(TimeSource::load_sync_record(self))
+    }
+
+    fn store_offset_dyn(&mut self, offset: SignedDuration) -> BoxFuture<'_, Result<(), Self::Err>> {
+        Box::pin(TimesimpClient::store_offset(self, offset))
+    }
+
+    fn query_server_dyn(&self, request: Request) -> BoxFuture<'_, Result<Response, Self::Err>> {
+        Box::pin(TimesimpClient::query_server(self, request))
+    }
+
+    fn sleep_dyn(&self, duration: Duration) -> BoxFuture<'_, ()> {
+        Box::pin(TimesimpClient::sleep(self, duration))
+    }
+
+    fn store_sync_record_dyn(
+        &mut self,
+        record: SyncRecord,
+    ) -> BoxFuture<'_, Result<(), Self::Err>> {
+        Box::pin(TimesimpClient::store_sync_record(self, record))
+    }
+
+    fn store_samples_dyn(&mut self, samples: Vec<Sample>) -> BoxFuture<'_, Result<(), Self::Err>> {
+        Box::pin(TimesimpClient::store_samples(self, samples))
+    }
+
+    fn load_samples_dyn(&self) -> BoxFuture<'_, Result<Vec<Sample>, Self::Err>> {
+        Box::pin(TimesimpClient::load_samples(self))
+    }
+}
+
+impl<E: std::error::Error> TimeSource for Box<dyn DynTimesimpClient<Err = E> + '_> {
+    type Err = E;
+
+    async fn load_offset(&self) -> Result<Option<SignedDuration>, Self::Err> {
+        (**self).load_offset_dyn().await
+    }
+
+    fn clock(&self) -> &dyn Clock {
+        (**self).clock_dyn()
+    }
+
+    async fn load_sync_record(&self) -> Result<Option<SyncRecord>, Self::Err> {
+        (**self).load_sync_record_dyn().await
+    }
+}
+
+impl<E: std::error::Error> TimesimpClient for Box<dyn DynTimesimpClient<Err = E> + '_> {
+    async fn store_offset(&mut self, offset: SignedDuration) -> Result<(), Self::Err> {
+        (**self).store_offset_dyn(offset).await
+    }
+
+    async fn query_server(&self, request: Request) -> Result<Response, Self::Err> {
+        (**self).query_server_dyn(request).await
+    }
+
+    async fn sleep(&self, duration: Duration) {
+        (**self).sleep_dyn(duration).await;
+    }
+
+    async fn store_sync_record(&mut self, record: SyncRecord) -> Result<(), Self::Err> {
+        (**self).store_sync_record_dyn(record).await
+    }
+
+    async fn store_samples(&mut self, samples: Vec<Sample>) -> Result<(), Self::Err> {
+        (**self).store_samples_dyn(samples).await
+    }
+
+    async fn load_samples(&self) -> Result<Vec<Sample>, Self::Err> {
+        (**self).load_samples_dyn().await
+    }
+}
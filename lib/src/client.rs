@@ -0,0 +1,207 @@
+use std::time::Duration;
+
+use jiff::{SignedDuration, Timestamp};
+
+use crate::{
+    Request, Response, Sample, Settings, SyncError, SyncRecord, SyncReport, TimeBounds, TimeSource,
+    attempt, sampling,
+};
+
+/// A time sync client.
+///
+/// You must implement the three required functions and not override the others, except for the
+/// optional storage hooks, which enable additional features.
+///
+/// Then, use `attempt_sync()` to sync with a server.
+#[allow(async_fn_in_trait)]
+pub trait TimesimpClient: TimeSource {
+    /// Store the current time offset.
+    ///
+    /// This must store the given time offset, typically in some kind of database.
+    ///
+    /// As this is given a `SignedDuration`, you are free to store whatever precision you wish, so
+    /// long as `load_offset()` agrees. Microseconds should be enough for most purposes.
+    ///
+    /// Additionally, once `store_offset` has been called once, `load_offset` should return `Some`.
+    async fn store_offset(&mut self, offset: SignedDuration) -> Result<(), Self::Err>;
+
+    /// Query a timesimp server endpoint.
+    ///
+    /// This must query in some manner a timesimp server, by sending the given [`Request`] and
+    /// obtaining a [`Response`]. Both [`Request`] and [`Response`] can be parsed from and
+    /// serialized to bytes. The query implementation should do as little else as possible to
+    /// avoid adding unnecessary latency.
+    ///
+    /// If using a connecting protocol, such as TCP or QUIC, it's recommended to keep the
+    /// connection alive if practicable, with a timeout longer than the longest gap between two
+    /// samples: the [`Settings.jitter`](Settings) value with uniform spacing, but up to twice that
+    /// with [`Spacing::Fixed`](crate::Spacing::Fixed), and five times that with
+    /// [`Spacing::Exponential`](crate::Spacing::Exponential). That should result in all but the
+    /// first sample being approximately a single round trip, eliminating the handshake delay.
+    async fn query_server(&self, request: Request) -> Result<Response, Self::Err>;
+
+    /// Sleep for a [`Duration`].
+    ///
+    /// This is usually something like `tokio::time::sleep` or equivalent. As it's given `self`, it
+    /// can use a timer or runtime handle held by the client, or advance simulated time to go
+    /// with a simulated [`clock()`](TimeSource::clock).
+    async fn sleep(&self, duration: Duration);
+
+    /// Store the record of the last successful sync.
+    ///
+    /// Optional: by default this does nothing. Implement it and `load_sync_record()` to enable
+    /// [`adjusted_bounds()`](TimeSource::adjusted_bounds).
+    ///
+    /// As with the offset, this is typically stored in some kind of database.
+    async fn store_sync_record(&mut self, record: SyncRecord) -> Result<(), Self::Err> {
+        let _ = record;
+        Ok(())
+    }
+
+    /// Store the sample history.
+    ///
+    /// Optional: by default this does nothing. Implement it and `load_samples()` to use
+    /// [`Settings.history`](Settings). The samples given replace any previously stored.
+    async fn store_samples(&mut self, samples: Vec<Sample>) -> Result<(), Self::Err> {
+        let _ = samples;
+        Ok(())
+    }
+
+    /// Load the sample history.
+    ///
+    /// Optional: by default this returns nothing. This must return the last samples given to
+    /// `store_samples()`, or nothing if there's none.
+    async fn load_samples(&self) -> Result<Vec<Sample>, Self::Err> {
+        Ok(Vec::new())
+    }
+
+    /// The main client state driver. Call this in a loop.
+    ///
+    /// You're expected to sleep for a while after calling this, or to run it on a schedule. Take
+    /// care to compute your schedule on your raw system monotonic clock or equivalent, so it does
+    /// not get influenced by the offset, which could make it jump around or even spin. The
+    /// [`Scheduler`](crate::Scheduler) can pick the intervals for you.
+    ///
+    /// If `load_offset()` returns `Ok(None)`, this method will attempt to `store_offset()` the
+    /// first delta it gets from the server. This lets you get an "accurate enough" timestamp
+    /// pretty quickly, instead of waiting for a full round of samples.
+    ///
+    /// Errors from `query_server()` are logged using tracing, and given back in the [`SyncError`]
+    /// if the attempt fails because of them: because every query failed, or because not enough
+    /// samples were obtained to have enough confidence in the result. The failed attempt is
+    /// counted in the sync record, if there's one: see [`sync_state()`](TimeSource::sync_state)
+    /// for holdover.
+    ///
+    /// If the calculated offset is outside the limits set in the [`Settings`], it's not stored,
+    /// and an error is returned.
+    ///
+    /// Do not override.
+    ///
+    /// # Example
+    ///
+    /// ```ignore
+    /// loop {
+    ///     match simp.attempt_sync(Settings::default()).await {
+    ///         Err(err) => eprintln!("{err}"),
+    ///         Ok(offset) => {
+    ///             println!("Obtained offset: {offset:?}");
+    ///             println!("The adjusted time is {}", simp.adjusted_timestamp().unwrap());
+    ///         }
+    ///     }
+    ///     sleep(Duration::from_secs(60));
+    /// }
+    /// ```
+    async fn attempt_sync(
+        &mut self,
+        settings: Settings,
+    ) -> Result<SignedDuration, SyncError<Self::Err>> {
+        self.attempt_sync_report(settings)
+            .await
+            .map(|report| report.offset)
+    }
+
+    /// The main client state driver, with a report of the attempt.
+    ///
+    /// Do not override.
+    ///
+    /// This is the same as [`attempt_sync()`](TimesimpClient::attempt_sync), but also reports how
+    /// many samples were obtained and how many failed.
+    async fn attempt_sync_report(
+        &mut self,
+        settings: Settings,
+    ) -> Result<SyncReport, SyncError<Self::Err>> {
+        let current_offset = self.load_offset().await.map_err(SyncError::Storage)?;
+        tracing::trace!(?settings, ?current_offset, "starting delta collection");
+
+        let round = sampling::collect(self, settings, true, async |simp, request| {
+            simp.query_server(request).await
+        })
+        .await
+        .map_err(SyncError::Storage)?;
+
+        let history = match settings.history {
+            Some(_) => self.load_samples().await.map_err(SyncError::Storage)?,
+            None => Vec::new(),
+        };
+        let record = self.load_sync_record().await.map_err(SyncError::Storage)?;
+        let conclusion = attempt::conclude(
+            round,
+            settings,
+            current_offset,
+            history,
+            record,
+            self.clock().now(),
+        );
+
+        if let Some(history) = conclusion.history {
+            self.store_samples(history)
+                .await
+                .map_err(SyncError::Storage)?;
+        }
+        if let Some(offset) = conclusion.offset {
+            self.store_offset(offset)
+                .await
+                .map_err(SyncError::Storage)?;
+        }
+        if let Some(record) = conclusion.record {
+            self.store_sync_record(record)
+                .await
+                .map_err(SyncError::Storage)?;
+        }
+
+        conclusion.result
+    }
+
+    /// Wait until the true time is definitely after a timestamp.
+    ///
+    /// Do not override.
+    ///
+    /// This sleeps until the earliest bound of [`adjusted_bounds()`](TimeSource::adjusted_bounds)
+    /// is after the given timestamp, and returns those bounds. That's useful to order events
+    /// across machines, similarly to the commit-wait of Spanner's TrueTime.
+    ///
+    /// Returns `None` straight away if there's no sync record.
+    async fn wait_until_after(
+        &self,
+        timestamp: Timestamp,
+        drift: u32,
+    ) -> Result<Option<TimeBounds>, Self::Err> {
+        loop {
+            let Some(bounds) = self.adjusted_bounds(drift).await? else {
+                return Ok(None);
+            };
+
+            if bounds.is_after(timestamp) {
+                return Ok(Some(bounds));
+            }
+
+            // the bounds widen while we sleep, so this may take a few rounds
+            let wait = timestamp
+                .duration_since(bounds.earliest)
+                .unsigned_abs()
+                .max(Duration::from_millis(1));
+            tracing::trace!(?wait, ?bounds, "waiting until definitely after timestamp");
+            self.sleep(wait).await;
+        }
+    }
+}
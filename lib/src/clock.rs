@@ -0,0 +1,98 @@
+use std::time::Instant;
+#[cfg(any(target_os = "linux", target_os = "android"))]
+use std::{sync::LazyLock, time::Duration};
+
+use jiff::Timestamp;
+
+/// A source of time.
+///
+/// All of timesimp's client and server logic reads the time through the
+/// [`TimeSource::clock()`](crate::TimeSource::clock), which defaults to the [`SystemClock`].
+/// Override it to run on another timescale (like TAI), or on simulated time in tests.
+///
+/// Clocks may be read from any thread, so they must be `Send` and `Sync`.
+pub trait Clock: Send + Sync {
+    /// The current time.
+    ///
+    /// This is the clock that offsets are relative to.
+    fn now(&self) -> Timestamp;
+
+    /// The current monotonic instant.
+    ///
+    /// This is used to time round trips, deadlines, and elapsed time, so it must never go
+    /// backwards. A simulated clock can return a fixed base instant plus the simulated elapsed
+    /// time.
+    fn instant(&self) -> Instant;
+
+    /// The current instant on a clock that keeps counting while the system is suspended.
+    ///
+    /// This anchors the [`SyncedClock`](crate::SyncedClock), so that it doesn't fall behind after
+    /// a suspend. It must never go backwards either.
+    ///
+    /// Optional: by default this is the [`instant()`](Clock::instant).
+    fn boot_instant(&self) -> Instant {
+        self.instant()
+    }
+}
+
+/// The system clock.
+///
+/// This reads the time with [`Timestamp::now()`] and [`Instant::now()`]. On Linux and Android,
+/// the [`boot_instant()`](Clock::boot_instant) follows `CLOCK_BOOTTIME`, which keeps counting
+/// while the system is suspended; elsewhere it's the [`Instant::now()`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Timestamp {
+        Timestamp::now()
+    }
+
+    fn instant(&self) -> Instant {
+        Instant::now()
+    }
+
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    fn boot_instant(&self) -> Instant {
+        // an Instant can't be made from a raw time, so offset one by the boot time elapsed since
+        static BASE: LazyLock<(Instant, Duration)> =
+            LazyLock::new(|| (Instant::now(), boot_time()));
+        let (instant, boot) = *BASE;
+        instant + boot_time().saturating_sub(boot)
+    }
+}
+
+/// The time since boot, including the time suspended.
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn boot_time() -> Duration {
+    let mut time = libc::timespec {
+        tv_sec: 0,
+        tv_nsec: 0,
+    };
+    // SAFETY: the timespec is valid for writes, and CLOCK_BOOTTIME exists on these platforms
+    let ret = unsafe { libc::clock_gettime(libc::CLOCK_BOOTTIME, &mut time) };
+    assert_eq!(ret, 0, "clock_gettime(CLOCK_BOOTTIME) failed");
+    Duration::new(time.tv_sec as u64, time.tv_nsec as u32)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{thread::sleep, time::Duration};
+
+    use super::*;
+
+    #[test]
+    fn boot_instant_follows_instant() {
+        let instant = SystemClock.instant();
+        let boot = SystemClock.boot_instant();
+        sleep(Duration::from_millis(10));
+        let elapsed = SystemClock.instant().duration_since(instant);
+        let boot_elapsed = SystemClock.boot_instant().duration_since(boot);
+
+        // the system isn't suspended during the test, so both clocks advance together
+        assert!(
+            boot_elapsed.abs_diff(elapsed) < Duration::from_millis(2),
+            "{boot_elapsed:?} != {elapsed:?}"
+        );
+    }
+}
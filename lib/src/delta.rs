@@ -2,10 +2,11 @@ use std::time::Duration;
 
 use jiff::{SignedDuration, SpanRelativeTo, Timestamp};
 
-use crate::Response;
+use crate::{Asymmetry, Response};
 
 #[derive(Debug, Clone, Copy)]
 pub(crate) struct Delta {
+    pub(crate) at: Timestamp,
     pub(crate) latency: Duration,
     pub(crate) delta: SignedDuration,
 }
@@ -15,37 +16,46 @@ impl Delta {
     ///
     /// The idea is to compute the round trip time, then {half that + the sent time} calculates the
     /// local time at the moment the server stamped the response. Then comparing that moment to the
-    /// server time gives us the delta to apply to the local clock.
+    /// server time gives us the delta to apply to the local clock. If the trip is known to be
+    /// asymmetric, the outbound leg is used instead of half the round trip.
     ///
-    /// The tests below have diagrams that may make things clearer.
+    /// The round trip time is measured on the monotonic clock, so it can't be corrupted by the
+    /// wall clock being stepped during the sample; the wall clock is only used to anchor the
+    /// midpoint, through the sent time.
     ///
-    /// Returns None if latency is negative, ie local clock went backwards.
+    /// The tests below have diagrams that may make things clearer.
     #[tracing::instrument(level = "trace")]
-    pub(crate) fn new(response: Response, current: Timestamp) -> Option<Self> {
-        let latency = (current - response.client)
-            .to_duration(SpanRelativeTo::days_are_24_hours())
-            .unwrap()
-            / 2;
-        let local_at_midpoint = response.client + latency;
-        let delta = (response.server - local_at_midpoint)
+    pub(crate) fn new(response: Response, round_trip: Duration, asymmetry: Asymmetry) -> Self {
+        // UNWRAP: a round trip would need to take 292 years to overflow
+        let round_trip = SignedDuration::try_from(round_trip).unwrap();
+        let latency = round_trip / 2;
+        let local_at_server = response.client + asymmetry.outbound(round_trip);
+        let delta = (response.server - local_at_server)
             .to_duration(SpanRelativeTo::days_are_24_hours())
             .unwrap();
         tracing::trace!(
             ?latency,
-            ?local_at_midpoint,
+            ?local_at_server,
             ?delta,
             "response processing internals"
         );
 
-        Duration::try_from(latency)
-            .ok()
-            .map(|latency| Self { latency, delta })
+        Self {
+            at: response.client,
+            latency: latency.unsigned_abs(),
+            delta,
+        }
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use std::{thread::sleep, time::Duration};
+    use std::{
+        thread::sleep,
+        time::{Duration, Instant},
+    };
+
+    use jiff::Timestamp;
 
     use super::*;
 
@@ -66,14 +76,14 @@ mod tests {
 
         let client_time = Timestamp::new(0, 300).unwrap();
         let server_time = Timestamp::new(0, 200).unwrap();
-        let round_trip = SignedDuration::from_nanos(600);
+        let round_trip = Duration::from_nanos(600);
 
         let response = Response {
             client: client_time,
             server: server_time,
         };
 
-        let processed = Delta::new(response, client_time + round_trip).unwrap();
+        let processed = Delta::new(response, round_trip, Asymmetry::Symmetric);
 
         assert_eq!(processed.latency, Duration::from_nanos(300), "latency");
         assert_eq!(processed.delta, SignedDuration::from_nanos(-400), "delta");
@@ -96,14 +106,14 @@ mod tests {
 
         let client_time = Timestamp::new(0, 500).unwrap();
         let server_time = Timestamp::new(0, 1200).unwrap();
-        let round_trip = SignedDuration::from_nanos(800);
+        let round_trip = Duration::from_nanos(800);
 
         let response = Response {
             client: client_time,
             server: server_time,
         };
 
-        let processed = Delta::new(response, client_time + round_trip).unwrap();
+        let processed = Delta::new(response, round_trip, Asymmetry::Symmetric);
 
         assert_eq!(processed.latency, Duration::from_nanos(400), "latency");
         assert_eq!(processed.delta, SignedDuration::from_nanos(300), "delta");
@@ -126,48 +136,146 @@ mod tests {
 
         let client_time = Timestamp::new(0, 500).unwrap();
         let server_time = Timestamp::new(0, 700).unwrap();
-        let round_trip = SignedDuration::from_nanos(400);
+        let round_trip = Duration::from_nanos(400);
 
         let response = Response {
             client: client_time,
             server: server_time,
         };
 
-        let processed = Delta::new(response, client_time + round_trip).unwrap();
+        let processed = Delta::new(response, round_trip, Asymmetry::Symmetric);
 
         assert_eq!(processed.latency, Duration::from_nanos(200), "latency");
         assert_eq!(processed.delta, SignedDuration::from_nanos(0), "delta");
     }
 
     #[test]
-    fn clock_went_backwards() {
-        let sent_time = Timestamp::new(0, 500).unwrap();
-        let server_time = Timestamp::new(0, 700).unwrap();
-        let arrive_time = Timestamp::new(0, 200).unwrap();
+    fn slow_uplink_ratio() {
+        /*
+            c=5 |\    |
+                | \   |
+                |  \  |
+                | 6 \ |
+                |    \|
+            c=11|-----| s=14
+            s=14|    /|       -- offset=+3
+                | 4 / |
+                |  /  |
+                | /   |
+        */
+
+        let client_time = Timestamp::new(0, 500).unwrap();
+        let server_time = Timestamp::new(0, 1400).unwrap();
+        let round_trip = Duration::from_nanos(1000);
 
         let response = Response {
-            client: sent_time,
+            client: client_time,
+            server: server_time,
+        };
+
+        let asymmetry = Asymmetry::Ratio {
+            outbound: 3,
+            inbound: 2,
+        };
+        let processed = Delta::new(response, round_trip, asymmetry);
+
+        assert_eq!(processed.latency, Duration::from_nanos(500), "latency");
+        assert_eq!(processed.delta, SignedDuration::from_nanos(300), "delta");
+    }
+
+    #[test]
+    fn slow_uplink_bias() {
+        /*
+            c=5 |\    |
+                | \   |
+                |  \  |
+                | 6 \ |
+                |    \|
+            c=11|-----| s=14
+            s=14|    /|       -- offset=+3
+                | 4 / |
+                |  /  |
+                | /   |
+        */
+
+        let client_time = Timestamp::new(0, 500).unwrap();
+        let server_time = Timestamp::new(0, 1400).unwrap();
+        let round_trip = Duration::from_nanos(1000);
+
+        let response = Response {
+            client: client_time,
+            server: server_time,
+        };
+
+        let asymmetry = Asymmetry::Bias(SignedDuration::from_nanos(200));
+        let processed = Delta::new(response, round_trip, asymmetry);
+
+        assert_eq!(processed.latency, Duration::from_nanos(500), "latency");
+        assert_eq!(processed.delta, SignedDuration::from_nanos(300), "delta");
+    }
+
+    #[test]
+    fn bias_larger_than_round_trip() {
+        let client_time = Timestamp::new(0, 500).unwrap();
+        let server_time = Timestamp::new(0, 1500).unwrap();
+        let round_trip = Duration::from_nanos(1000);
+
+        let response = Response {
+            client: client_time,
+            server: server_time,
+        };
+
+        let asymmetry = Asymmetry::Bias(SignedDuration::from_nanos(5000));
+        let processed = Delta::new(response, round_trip, asymmetry);
+        assert_eq!(processed.delta, SignedDuration::ZERO, "delta");
+
+        let asymmetry = Asymmetry::Bias(SignedDuration::from_nanos(-5000));
+        let processed = Delta::new(response, round_trip, asymmetry);
+        assert_eq!(processed.delta, SignedDuration::from_nanos(1000), "delta");
+
+        let asymmetry = Asymmetry::Bias(SignedDuration::MAX);
+        let processed = Delta::new(response, round_trip, asymmetry);
+        assert_eq!(processed.delta, SignedDuration::ZERO, "delta");
+
+        let asymmetry = Asymmetry::Bias(SignedDuration::MIN);
+        let processed = Delta::new(response, round_trip, asymmetry);
+        assert_eq!(processed.delta, SignedDuration::from_nanos(1000), "delta");
+    }
+
+    #[test]
+    fn zero_ratio_is_symmetric() {
+        let client_time = Timestamp::new(0, 500).unwrap();
+        let server_time = Timestamp::new(0, 1000).unwrap();
+        let round_trip = Duration::from_nanos(1000);
+
+        let response = Response {
+            client: client_time,
             server: server_time,
         };
 
-        let proc = Delta::new(response, arrive_time);
-        assert!(proc.is_none(), "{proc:?}");
+        let asymmetry = Asymmetry::Ratio {
+            outbound: 0,
+            inbound: 0,
+        };
+        let processed = Delta::new(response, round_trip, asymmetry);
+        assert_eq!(processed.delta, SignedDuration::ZERO, "delta");
     }
 
     #[test]
     fn with_sleep() {
+        let sent_instant = Instant::now();
         let sent_time = Timestamp::now();
         sleep(Duration::from_millis(10));
         let server_time = Timestamp::now();
         sleep(Duration::from_millis(10));
-        let arrive_time = Timestamp::now();
+        let round_trip = sent_instant.elapsed();
 
         let response = Response {
             client: sent_time,
             server: server_time,
         };
 
-        let processed = Delta::new(response, arrive_time).unwrap();
+        let processed = Delta::new(response, round_trip, Asymmetry::Symmetric);
 
         if cfg!(target_os = "linux") {
             assert!(
@@ -0,0 +1,118 @@
+use std::time::Duration;
+
+use jiff::SignedDuration;
+
+use crate::ServerEstimate;
+
+/// Error from a sync attempt.
+///
+/// When a sync attempt fails, the offset it calculated, if any, is not stored, and the failed
+/// attempt is counted in the sync record, if there's one. However, if no offset was stored before
+/// the attempt, the first delta obtained may already have been stored as the offset during it; see
+/// [`attempt_sync()`](crate::TimesimpClient::attempt_sync).
+///
+/// The errors from the queries that failed are given back where relevant; note that they're
+/// dropped when the sync succeeds despite some queries failing.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum SyncError<E> {
+    /// Loading or storing failed.
+    ///
+    /// This includes storing the initial delta when there's no offset stored yet.
+    #[error("storage failed")]
+    Storage(#[source] E),
+
+    /// Every query to the server failed, before the deadline if there's one.
+    #[error("all queries failed ({} errors, {timeouts} timed out)", .errors.len())]
+    AllQueriesFailed {
+        /// The errors from the queries, in the order they failed.
+        ///
+        /// Queries that timed out have no error.
+        errors: Vec<E>,
+
+        /// How many queries timed out.
+        timeouts: usize,
+    },
+
+    /// The local clock went backwards during the sync.
+    ///
+    /// Deltas obtained before and after the step are relative to different clocks, so none are
+    /// used. Sync again once the clock is stable.
+    #[error("the clock went backwards by {by:?} during the sync")]
+    ClockWentBackwards {
+        /// How far the clock went backwards.
+        by: Duration,
+    },
+
+    /// Not enough samples were obtained to have confidence in the result.
+    ///
+    /// At least 3 samples are needed, after discarding the first one if there's an even number.
+    #[error("too few samples for confidence: {samples} obtained")]
+    TooFewInliers {
+        /// How many samples were obtained, including the [history](crate::Settings::history).
+        samples: usize,
+
+        /// The errors from the queries that failed, in the order they failed.
+        errors: Vec<E>,
+
+        /// How many queries timed out.
+        timeouts: usize,
+
+        /// Whether the attempt was cut short by its deadline.
+        ///
+        /// If so, the deadline was too short to gather enough samples.
+        deadline_reached: bool,
+    },
+
+    /// The calculated offset is larger than [`Settings.max_offset`](crate::Settings).
+    ///
+    /// It has not been stored.
+    #[error("offset {offset:?} is larger than the maximum of {max:?}")]
+    OffsetTooLarge {
+        /// The calculated offset.
+        offset: SignedDuration,
+
+        /// The maximum allowed.
+        max: Duration,
+    },
+
+    /// The calculated offset is too far from the current one, as per
+    /// [`Settings.max_change`](crate::Settings).
+    ///
+    /// It has not been stored.
+    #[error(
+        "offset {offset:?} is too far from the current offset {current:?} (maximum change {max:?})"
+    )]
+    ChangeTooLarge {
+        /// The calculated offset.
+        offset: SignedDuration,
+
+        /// The offset currently stored.
+        current: SignedDuration,
+
+        /// The maximum change allowed.
+        max: Duration,
+    },
+
+    /// Not enough servers agreed in a multi-server sync.
+    ///
+    /// The servers that agree must be a majority of all the servers, including those that couldn't
+    /// be sampled.
+    #[error(
+        "no majority of {servers} servers agree ({} disagree, {} unreachable)",
+        .falsetickers.len(),
+        .unreachable.len()
+    )]
+    NoMajority {
+        /// How many servers there are.
+        servers: usize,
+
+        /// The servers that could be sampled, none of which were agreed with by a majority.
+        falsetickers: Vec<ServerEstimate>,
+
+        /// The servers that didn't give enough samples to have confidence in their offset.
+        unreachable: Vec<usize>,
+
+        /// The errors from the queries that failed, by server index.
+        errors: Vec<Vec<E>>,
+    },
+}
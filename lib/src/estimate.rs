@@ -0,0 +1,283 @@
+use std::time::Duration;
+
+use jiff::SignedDuration;
+
+use crate::{Delta, OutlierFilter};
+
+/// The statistical result of a round of samples.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct Estimate {
+    /// The estimated offset to apply to the local clock.
+    pub(crate) offset: SignedDuration,
+
+    /// The lowest one-way latency seen among the samples.
+    ///
+    /// As the trip is assumed symmetric, the true offset may be off by up to this much.
+    pub(crate) latency: Duration,
+
+    /// The standard deviation of the deltas that were kept.
+    pub(crate) dispersion: Duration,
+
+    /// How many deltas were kept.
+    pub(crate) inliers: usize,
+}
+
+impl Estimate {
+    /// Compute an estimate from the raw deltas of a round of samples.
+    ///
+    /// If there's an even number of deltas, the first one is discarded, as it is most likely to
+    /// be an outlier due to connection establishment. Returns None if there's fewer than 3 left.
+    ///
+    /// The deltas are sorted by latency, outliers are eliminated with the given filter, and the
+    /// estimate is the mean of the remaining ones.
+    pub(crate) fn new(mut responses: Vec<Delta>, filter: OutlierFilter) -> Option<Self> {
+        if !responses.is_empty() && responses.len().is_multiple_of(2) {
+            responses.remove(0);
+        }
+
+        if responses.len() < 3 {
+            tracing::debug!(
+                count = responses.len(),
+                "not enough responses for confidence"
+            );
+            return None;
+        }
+
+        responses.sort_by_key(|r| r.latency);
+        let latency = responses[0].latency;
+        let deltas = responses
+            .iter()
+            .map(|r| r.delta.as_millis_f64())
+            .collect::<Vec<_>>();
+        tracing::trace!(?deltas, "response deltas sorted by latency");
+
+        let inliers = match filter {
+            OutlierFilter::StdDev => {
+                let median_idx = deltas.len() / 2;
+                let median = deltas[median_idx];
+
+                let mean: f64 = deltas.iter().copied().sum::<f64>() / deltas.len() as f64;
+                let stddev = sample_stddev(&deltas, mean);
+                tracing::trace!(?median, ?mean, ?stddev, "statistics about response deltas");
+
+                within(&deltas, median - stddev, median + stddev)
+            }
+            OutlierFilter::Mad => {
+                let mut sorted = deltas.clone();
+                sorted.sort_by(f64::total_cmp);
+                let median = quantile(&sorted, 0.5);
+
+                let mut deviations = sorted
+                    .iter()
+                    .map(|d| (d - median).abs())
+                    .collect::<Vec<_>>();
+                deviations.sort_by(f64::total_cmp);
+                // scaled so it estimates the standard deviation for normally distributed deltas
+                let mad = quantile(&deviations, 0.5) * 1.4826;
+                tracing::trace!(?median, ?mad, "statistics about response deltas");
+
+                within(&deltas, median - 3.0 * mad, median + 3.0 * mad)
+            }
+            OutlierFilter::Iqr => {
+                let mut sorted = deltas.clone();
+                sorted.sort_by(f64::total_cmp);
+                let q1 = quantile(&sorted, 0.25);
+                let q3 = quantile(&sorted, 0.75);
+                let iqr = q3 - q1;
+                tracing::trace!(?q1, ?q3, ?iqr, "statistics about response deltas");
+
+                within(&deltas, q1 - 1.5 * iqr, q3 + 1.5 * iqr)
+            }
+        };
+        tracing::trace!(?inliers, "eliminated outliers");
+
+        let inlier_mean = inliers.iter().sum::<f64>() / (inliers.len() as f64);
+        Some(Self {
+            offset: SignedDuration::from_micros((inlier_mean * 1000.0) as i64),
+            latency,
+            dispersion: Duration::from_secs_f64(sample_stddev(&inliers, inlier_mean) / 1000.0),
+            inliers: inliers.len(),
+        })
+    }
+
+    /// The maximum error of this estimate.
+    ///
+    /// The true offset is expected to be within this much of the estimated offset.
+    pub(crate) fn error(&self) -> Duration {
+        self.latency + self.dispersion
+    }
+
+    /// The standard error of the estimated offset.
+    ///
+    /// Returns None if fewer than two deltas were kept, as their spread is then unknown.
+    pub(crate) fn standard_error(&self) -> Option<Duration> {
+        (self.inliers >= 2).then(|| self.dispersion.div_f64((self.inliers as f64).sqrt()))
+    }
+}
+
+/// The values within a range, inclusive.
+fn within(values: &[f64], low: f64, high: f64) -> Vec<f64> {
+    values
+        .iter()
+        .copied()
+        .filter(|d| *d >= low && *d <= high)
+        .collect()
+}
+
+/// A quantile of sorted values, interpolating linearly between them.
+fn quantile(sorted: &[f64], q: f64) -> f64 {
+    let pos = (sorted.len() - 1) as f64 * q;
+    let low = sorted[pos.floor() as usize];
+    let high = sorted[pos.ceil() as usize];
+    low + (high - low) * pos.fract()
+}
+
+/// Sample standard deviation.
+fn sample_stddev(values: &[f64], mean: f64) -> f64 {
+    if values.len() < 2 {
+        return 0.0;
+    }
+
+    let variance: f64 = values
+        .iter()
+        .copied()
+        .map(|d| (d - mean).powi(2))
+        .sum::<f64>()
+        / ((values.len() - 1) as f64);
+    variance.sqrt()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn delta(latency_ms: u64, delta_ms: i64) -> Delta {
+        Delta {
+            at: jiff::Timestamp::UNIX_EPOCH,
+            latency: Duration::from_millis(latency_ms),
+            delta: SignedDuration::from_millis(delta_ms),
+        }
+    }
+
+    #[test]
+    fn too_few() {
+        assert_eq!(Estimate::new(vec![], OutlierFilter::StdDev), None);
+        assert_eq!(
+            Estimate::new(vec![delta(1, 1), delta(1, 1)], OutlierFilter::StdDev),
+            None
+        );
+    }
+
+    #[test]
+    fn stable() {
+        let estimate = Estimate::new(
+            vec![delta(10, 100), delta(12, 100), delta(11, 100)],
+            OutlierFilter::StdDev,
+        )
+        .unwrap();
+        assert_eq!(estimate.offset, SignedDuration::from_millis(100));
+        assert_eq!(estimate.latency, Duration::from_millis(10));
+        assert_eq!(estimate.dispersion, Duration::ZERO);
+        assert_eq!(estimate.error(), Duration::from_millis(10));
+        assert_eq!(estimate.standard_error(), Some(Duration::ZERO));
+    }
+
+    #[test]
+    fn discards_first_of_even() {
+        let estimate = Estimate::new(
+            vec![
+                delta(50, 500),
+                delta(10, 100),
+                delta(12, 100),
+                delta(11, 100),
+            ],
+            OutlierFilter::StdDev,
+        )
+        .unwrap();
+        assert_eq!(estimate.offset, SignedDuration::from_millis(100));
+    }
+
+    #[test]
+    fn eliminates_outlier() {
+        let estimate = Estimate::new(
+            vec![
+                delta(10, 100),
+                delta(12, 102),
+                delta(11, 98),
+                delta(13, 101),
+                delta(14, 400),
+            ],
+            OutlierFilter::StdDev,
+        )
+        .unwrap();
+        assert!(
+            estimate.offset > SignedDuration::from_millis(98)
+                && estimate.offset < SignedDuration::from_millis(102),
+            "offset = {:?}",
+            estimate.offset
+        );
+        assert_eq!(estimate.inliers, 4);
+    }
+
+    /// Deltas sorted by latency, with two spikes among the slower ones.
+    fn spiky() -> Vec<Delta> {
+        vec![
+            delta(10, 100),
+            delta(11, 101),
+            delta(12, 99),
+            delta(13, 100),
+            delta(14, 102),
+            delta(15, 98),
+            delta(16, 101),
+            delta(17, 5000),
+            delta(18, 120),
+        ]
+    }
+
+    #[test]
+    fn stddev_lets_spikes_in() {
+        // the huge spike widens the window enough to let the smaller one in
+        let estimate = Estimate::new(spiky(), OutlierFilter::StdDev).unwrap();
+        assert_eq!(estimate.inliers, 8);
+        assert!(
+            estimate.offset > SignedDuration::from_millis(102),
+            "offset = {:?}",
+            estimate.offset
+        );
+    }
+
+    #[test]
+    fn mad_rejects_spikes() {
+        let estimate = Estimate::new(spiky(), OutlierFilter::Mad).unwrap();
+        assert_eq!(estimate.inliers, 7);
+        assert!(
+            estimate.offset > SignedDuration::from_millis(99)
+                && estimate.offset < SignedDuration::from_millis(101),
+            "offset = {:?}",
+            estimate.offset
+        );
+    }
+
+    #[test]
+    fn iqr_rejects_spikes() {
+        let estimate = Estimate::new(spiky(), OutlierFilter::Iqr).unwrap();
+        assert_eq!(estimate.inliers, 7);
+        assert!(
+            estimate.offset > SignedDuration::from_millis(99)
+                && estimate.offset < SignedDuration::from_millis(101),
+            "offset = {:?}",
+            estimate.offset
+        );
+    }
+
+    #[test]
+    fn robust_filters_keep_stable() {
+        for filter in [OutlierFilter::Mad, OutlierFilter::Iqr] {
+            let estimate =
+                Estimate::new(vec![delta(10, 100), delta(12, 100), delta(11, 100)], filter)
+                    .unwrap();
+            assert_eq!(estimate.offset, SignedDuration::from_millis(100));
+            assert_eq!(estimate.inliers, 3);
+        }
+    }
+}
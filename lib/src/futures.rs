@@ -0,0 +1,82 @@
+use std::{
+    future::poll_fn,
+    pin::{Pin, pin},
+    task::Poll,
+};
+
+/// Which of two raced futures finished first.
+#[derive(Debug)]
+pub(crate) enum Either<A, B> {
+    Left(A),
+    Right(B),
+}
+
+/// Poll two futures concurrently, and return the output of whichever finishes first.
+///
+/// The other future is dropped. If both are ready on the same poll, `left` wins.
+pub(crate) async fn race<A, B>(
+    left: impl Future<Output = A>,
+    right: impl Future<Output = B>,
+) -> Either<A, B> {
+    let mut left = pin!(left);
+    let mut right = pin!(right);
+    poll_fn(|cx| {
+        if let Poll::Ready(output) = left.as_mut().poll(cx) {
+            return Poll::Ready(Either::Left(output));
+        }
+        if let Poll::Ready(output) = right.as_mut().poll(cx) {
+            return Poll::Ready(Either::Right(output));
+        }
+        Poll::Pending
+    })
+    .await
+}
+
+/// A set of futures of the same type, polled concurrently.
+///
+/// Outputs are obtained in the order the futures complete. Each future is pushed with a key, which
+/// can be used to drop it before it completes.
+pub(crate) struct Unordered<K, F> {
+    futures: Vec<(K, Pin<Box<F>>)>,
+}
+
+impl<K, F: Future> Unordered<K, F> {
+    pub(crate) fn new() -> Self {
+        Self {
+            futures: Vec::new(),
+        }
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.futures.len()
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.futures.is_empty()
+    }
+
+    pub(crate) fn push(&mut self, key: K, future: F) {
+        self.futures.push((key, Box::pin(future)));
+    }
+
+    /// Drop the futures whose key doesn't match the predicate.
+    pub(crate) fn retain(&mut self, mut keep: impl FnMut(&K) -> bool) {
+        self.futures.retain(|(key, _)| keep(key));
+    }
+
+    /// Wait for the next future to complete, and remove it from the set.
+    ///
+    /// If the set is empty, this never completes.
+    pub(crate) async fn next(&mut self) -> F::Output {
+        poll_fn(|cx| {
+            for i in 0..self.futures.len() {
+                if let Poll::Ready(output) = self.futures[i].1.as_mut().poll(cx) {
+                    drop(self.futures.swap_remove(i));
+                    return Poll::Ready(output);
+                }
+            }
+            Poll::Pending
+        })
+        .await
+    }
+}
@@ -0,0 +1,131 @@
+use std::time::Duration;
+
+use jiff::{SignedDuration, Timestamp};
+
+use crate::{Delta, History};
+
+/// A sample kept in the history across sync attempts.
+///
+/// Stored with [`TimesimpClient::store_samples()`](crate::TimesimpClient::store_samples) when
+/// [`Settings.history`](crate::Settings) is set.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Sample {
+    /// When the sample was taken, on the local clock.
+    pub at: Timestamp,
+
+    /// The one-way latency of the sample (half the round trip).
+    pub latency: Duration,
+
+    /// The delta between the local clock and the server's.
+    pub delta: SignedDuration,
+}
+
+impl From<Delta> for Sample {
+    fn from(delta: Delta) -> Self {
+        Self {
+            at: delta.at,
+            latency: delta.latency,
+            delta: delta.delta,
+        }
+    }
+}
+
+impl From<Sample> for Delta {
+    fn from(sample: Sample) -> Self {
+        Self {
+            at: sample.at,
+            latency: sample.latency,
+            delta: sample.delta,
+        }
+    }
+}
+
+/// Slide the history window over new samples.
+///
+/// Samples older than the window (or from the future, if the clock went backwards) are dropped,
+/// then the most recent ones are kept up to the maximum. The result is sorted by time.
+pub(crate) fn slide(
+    mut samples: Vec<Sample>,
+    new: impl IntoIterator<Item = Sample>,
+    history: History,
+    now: Timestamp,
+) -> Vec<Sample> {
+    samples.extend(new);
+    samples.retain(|sample| {
+        let age = now.duration_since(sample.at);
+        !age.is_negative() && age.unsigned_abs() <= history.window
+    });
+    samples.sort_by_key(|sample| sample.at);
+
+    let excess = samples.len().saturating_sub(history.max_samples.into());
+    samples.drain(..excess);
+    samples
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample(at: i64) -> Sample {
+        Sample {
+            at: Timestamp::from_second(at).unwrap(),
+            latency: Duration::from_millis(10),
+            delta: SignedDuration::from_millis(at),
+        }
+    }
+
+    fn history(window: u64, max_samples: u16) -> History {
+        History {
+            window: Duration::from_secs(window),
+            max_samples,
+        }
+    }
+
+    fn ats(samples: &[Sample]) -> Vec<i64> {
+        samples.iter().map(|s| s.at.as_second()).collect()
+    }
+
+    #[test]
+    fn appends() {
+        let slid = slide(
+            vec![sample(10), sample(20)],
+            [sample(30), sample(40)],
+            history(100, 10),
+            Timestamp::from_second(50).unwrap(),
+        );
+        assert_eq!(ats(&slid), [10, 20, 30, 40]);
+    }
+
+    #[test]
+    fn drops_old() {
+        let slid = slide(
+            vec![sample(10), sample(20)],
+            [sample(30), sample(40)],
+            history(35, 10),
+            Timestamp::from_second(50).unwrap(),
+        );
+        assert_eq!(ats(&slid), [20, 30, 40]);
+    }
+
+    #[test]
+    fn drops_future() {
+        let slid = slide(
+            vec![sample(90), sample(20)],
+            [sample(30), sample(40)],
+            history(100, 10),
+            Timestamp::from_second(50).unwrap(),
+        );
+        assert_eq!(ats(&slid), [20, 30, 40]);
+    }
+
+    #[test]
+    fn keeps_most_recent() {
+        let slid = slide(
+            vec![sample(10), sample(20)],
+            [sample(30), sample(40)],
+            history(100, 3),
+            Timestamp::from_second(50).unwrap(),
+        );
+        assert_eq!(ats(&slid), [20, 30, 40]);
+    }
+}
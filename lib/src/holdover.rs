@@ -0,0 +1,83 @@
+use std::time::Duration;
+
+use jiff::{SignedDuration, Timestamp};
+
+use crate::{SyncRecord, TimesimpClient};
+
+/// The state of synchronisation, including holdover.
+///
+/// When sync attempts fail, for example because the server is unreachable, the client is in
+/// holdover: it keeps extrapolating from the last good sync with the learned drift, and the
+/// uncertainty grows with time.
+///
+/// Obtained from [`TimeSource::sync_state()`](crate::TimeSource::sync_state).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SyncState {
+    /// How long it's been since the last good sync.
+    pub since_sync: Duration,
+
+    /// How many sync attempts failed since the last good sync.
+    pub failed_attempts: u32,
+
+    /// The offset, extrapolated from the last good sync with the learned drift.
+    pub offset: SignedDuration,
+
+    /// The learned drift, in parts per billion.
+    pub drift: Option<i64>,
+
+    /// The maximum error of the extrapolated offset.
+    ///
+    /// This grows with the time since the last good sync; see
+    /// [`SyncRecord::error_at()`](crate::SyncRecord::error_at).
+    pub error: Duration,
+}
+
+impl SyncState {
+    /// Whether the client is in holdover, because the last sync attempt failed.
+    pub fn in_holdover(&self) -> bool {
+        self.failed_attempts > 0
+    }
+
+    /// The state some time after the last good sync, assuming a `drift` in parts per billion.
+    pub(crate) fn from_record(record: SyncRecord, now: Timestamp, drift: u32) -> Self {
+        Self {
+            since_sync: now.duration_since(record.at).unsigned_abs(),
+            failed_attempts: record.failed_attempts,
+            offset: record.offset_at(now),
+            drift: record.drift,
+            error: record.error_at(now, drift),
+        }
+    }
+}
+
+/// Store the record of a successful sync.
+pub(crate) async fn record_sync<T: TimesimpClient + ?Sized>(
+    simp: &mut T,
+    offset: SignedDuration,
+    error: Duration,
+) -> Result<(), T::Err> {
+    let previous = simp.load_sync_record().await?;
+    let record = SyncRecord::after(previous, simp.clock().now(), offset, error);
+    tracing::trace!(?record, "storing sync record");
+    simp.store_sync_record(record).await
+}
+
+/// Count a failed sync attempt in the sync record, if there's one.
+pub(crate) async fn record_failure<T: TimesimpClient + ?Sized>(simp: &mut T) -> Result<(), T::Err> {
+    if let Some(record) = failed(simp.load_sync_record().await?) {
+        simp.store_sync_record(record).await?;
+    }
+
+    Ok(())
+}
+
+/// The sync record after a failed sync attempt, if there's one.
+pub(crate) fn failed(record: Option<SyncRecord>) -> Option<SyncRecord> {
+    let mut record = record?;
+    record.failed_attempts = record.failed_attempts.saturating_add(1);
+    tracing::debug!(
+        failed_attempts = record.failed_attempts,
+        "sync failed, in holdover"
+    );
+    Some(record)
+}
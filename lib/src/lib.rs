@@ -7,66 +7,65 @@
 //! achieve accuracies of 100ms or better, which is sufficient in many cases; my testing gets
 //! accuracies well below 5ms. The main limitation of the algorithm is that round-trip-time is
 //! assumed to be symmetric: if the forward trip time is different from the return trip time, then
-//! an error is induced equal to the value of the difference in trip times.
+//! an error is induced equal to the value of the difference in trip times. If you know the path to
+//! be consistently asymmetric, you can compensate for it with [`Settings.asymmetry`](Settings).
 //!
 //! This library provides a sans-io implementation: you bring in your async runtime, your transport,
-//! and your storage; timesimp gives you time offsets.
-//!
-//! If the local clock goes backward during a synchronisation, the invalid delta is discarded; this
-//! may cause the sync attempt to fail, especially if the `samples` count is lowered to its minimum
-//! of 3. This is a deliberate design decision: you should handle failure and retry, and the sync
-//! will proceed correctly when the clock is stable.
+//! and your storage; timesimp gives you time offsets. Implement [`TimeSource`] to load the offset,
+//! then [`TimesimpServer`] to answer clients, and/or [`TimesimpClient`] to sync with a server.
+//! To spawn syncs on a multi-threaded runtime from generic code, implement [`SendTimeSource`] and
+//! [`SendTimesimpClient`] or [`SendTimesimpServer`] instead, whose futures are `Send`. To choose
+//! between clients at runtime, use them as [`DynTimesimpClient`] trait objects.
+//!
+//! If you don't have an async runtime, drive a [`SyncSession`] from your own loop instead, or
+//! enable the `blocking` feature for blocking equivalents of the traits in the `blocking` module.
+//!
+//! Round trips are timed on the monotonic clock, so if the local clock is stepped during a
+//! synchronisation, the latency of the sample in flight is not corrupted. However, deltas obtained
+//! before and after the step are relative to different clocks. If the clock steps backwards, this
+//! is noticed from the request timestamps, and the attempt is abandoned with
+//! [`SyncError::ClockWentBackwards`]. A step forwards looks the same as time passing between
+//! samples, so it isn't detected: the outlier elimination may discard some deltas, or the resulting
+//! offset may be off until the next sync. This is a deliberate design decision: you should sync
+//! regularly, and the sync will proceed correctly when the clock is stable.
 //!
 //! [paper]: https://web.archive.org/web/20160310125700/http://mine-control.com/zack/timesync/timesync.html
 //!
 //! # Example
 //!
 //! ```no_run
-//! use std::{convert::Infallible, time::Duration};
+//! use std::convert::Infallible;
 //! use reqwest::{Client, Url};
-//! use timesimp::{SignedDuration, Timesimp};
+//! use timesimp::{Scheduler, SignedDuration, TimeSource, TimesimpClient, TimesimpServer};
 //!
 //! struct ServerSimp;
-//! impl Timesimp for ServerSimp {
+//! impl TimeSource for ServerSimp {
 //!     type Err = Infallible;
 //!
 //!     async fn load_offset(&self) -> Result<Option<SignedDuration>, Self::Err> {
 //!         // server time is correct
 //!         Ok(Some(SignedDuration::ZERO))
 //!     }
-//!
-//!     async fn store_offset(&mut self, _offset: SignedDuration) -> Result<(), Self::Err> {
-//!         // as time is correct, no need to store offset
-//!         unimplemented!()
-//!     }
-//!
-//!     async fn query_server(
-//!         &self,
-//!         _request: timesimp::Request,
-//!     ) -> Result<timesimp::Response, Self::Err> {
-//!         // server has no upstream timesimp
-//!         unimplemented!()
-//!     }
-//!
-//!     async fn sleep(duration: std::time::Duration) {
-//!         tokio::time::sleep(duration).await;
-//!     }
 //! }
 //!
-//! // Not shown: serving ServerSimp from a URL
+//! impl TimesimpServer for ServerSimp {}
+//!
+//! // Not shown: serving ServerSimp from a URL, with answer_client()
 //!
 //! struct ClientSimp {
 //!     offset: Option<SignedDuration>,
 //!     url: Url,
 //! }
 //!
-//! impl Timesimp for ClientSimp {
+//! impl TimeSource for ClientSimp {
 //!     type Err = reqwest::Error;
 //!
 //!     async fn load_offset(&self) -> Result<Option<SignedDuration>, Self::Err> {
 //!         Ok(self.offset)
 //!     }
+//! }
 //!
+//! impl TimesimpClient for ClientSimp {
 //!     async fn store_offset(&mut self, offset: SignedDuration) -> Result<(), Self::Err> {
 //!         self.offset = Some(offset);
 //!         Ok(())
@@ -87,7 +86,7 @@
 //!         Ok(timesimp::Response::try_from(&resp[..]).unwrap())
 //!     }
 //!
-//!     async fn sleep(duration: std::time::Duration) {
+//!     async fn sleep(&self, duration: std::time::Duration) {
 //!         tokio::time::sleep(duration).await;
 //!     }
 //! }
@@ -99,237 +98,87 @@
 //!         url: "https://timesimp.server".try_into().unwrap(),
 //!     };
 //!
+//!     let mut scheduler = Scheduler::default();
 //!     loop {
-//!         if let Ok(Some(offset)) = client.attempt_sync(Default::default()).await {
+//!         let offset = client.attempt_sync(Default::default()).await.ok();
+//!         if let Some(offset) = offset {
 //!             println!(
 //!                 "Received offset: {offset:?}; current time is {}",
 //!                 client.adjusted_timestamp().await.unwrap(),
 //!             );
 //!         }
-//!         tokio::time::sleep(Duration::from_secs(300)).await;
+//!         tokio::time::sleep(scheduler.update(offset)).await;
 //!     }
 //! }
 //! ```
 
-use std::time::Duration;
-
 pub use jiff::{SignedDuration, Timestamp};
 
-mod delta;
-use delta::*;
+#[cfg(feature = "blocking")]
+pub mod blocking;
 
-mod messages;
-pub use messages::*;
-
-mod settings;
-pub use settings::*;
+mod attempt;
 
-/// A time sync client and/or server.
-///
-/// You must implement the four required functions and not override the others.
-///
-/// Then, use `answer_client()` to implement a time sync server, and/or use `attempt_sync()` to
-/// implement a time sync client.
-#[allow(async_fn_in_trait)]
-pub trait Timesimp {
-    /// Error for your required methods.
-    type Err: std::error::Error;
+mod boxed;
+pub use boxed::*;
 
-    /// Load the current time offset.
-    ///
-    /// This must return the current stored time offset, or `None` if no time offset is currently
-    /// stored.
-    ///
-    /// As this expects a `SignedDuration`, you are free to load whatever precision you wish, so
-    /// long as `store_offset()` agrees. Microseconds should be enough for most purposes.
-    async fn load_offset(&self) -> Result<Option<SignedDuration>, Self::Err>;
+mod bounds;
+pub use bounds::*;
 
-    /// Store the current time offset.
-    ///
-    /// This must store the given time offset, typically in some kind of database.
-    ///
-    /// As this is given a `SignedDuration`, you are free to store whatever precision you wish, so
-    /// long as `load_offset()` agrees. Microseconds should be enough for most purposes.
-    ///
-    /// Additionally, once `store_offset` has been called once, `load_offset` should return `Some`.
-    async fn store_offset(&mut self, offset: SignedDuration) -> Result<(), Self::Err>;
+mod client;
+pub use client::*;
 
-    /// Query a timesimp server endpoint.
-    ///
-    /// This must query in some manner a timesimp server, by sending the given [`Request`] and
-    /// obtaining a [`Response`]. Both [`Request`] and [`Response`] can be parsed from and
-    /// serialized to bytes. The query implementation should do as little else as possible to
-    /// avoid adding unnecessary latency.
-    ///
-    /// If using a connecting protocol, such as TCP or QUIC, it's recommended to keep the
-    /// connection alive if practicable, with a timeout longer than the
-    /// [`Settings.jitter`](Settings) value. That should result in all but the first sample being
-    /// approximately a single round trip, eliminating the handshake delay.
-    async fn query_server(&self, request: Request) -> Result<Response, Self::Err>;
+mod clock;
+pub use clock::*;
 
-    /// Sleep for a [`Duration`].
-    ///
-    /// This is usually something like `tokio::time::sleep` or equivalent.
-    async fn sleep(duration: Duration);
+mod delta;
+use delta::*;
 
-    /// Obtain an adjusted timestamp.
-    ///
-    /// Do not override.
-    ///
-    /// This simply loads the offset and applies it to the current local timestamp.
-    ///
-    /// It is provided as convenience for simple use; you may want to implement your own.
-    async fn adjusted_timestamp(&self) -> Result<Timestamp, Self::Err> {
-        let offset = self.load_offset().await?.unwrap_or_default();
-        Ok(Timestamp::now() + offset)
-    }
+mod error;
+pub use error::*;
 
-    /// The implementation of the server endpoint.
-    ///
-    /// Do not override.
-    ///
-    /// Use this in your server endpoint implementation. Both [`Request`] and [`Response`] can be
-    /// parsed from and serialized to bytes. The endpoint should do as little else as possible to
-    /// avoid adding unnecessary latency.
-    async fn answer_client(&self, request: Request) -> Result<Response, Self::Err> {
-        Ok(Response {
-            client: request.client,
-            server: self.adjusted_timestamp().await?,
-        })
-    }
+mod estimate;
+use estimate::*;
 
-    /// The main client state driver. Call this in a loop.
-    ///
-    /// You're expected to sleep for a while after calling this, or to run it on a schedule. Take
-    /// care to compute your schedule on your raw system monotonic clock or equivalent, so it does
-    /// not get influenced by the offset, which could make it jump around or even spin.
-    ///
-    /// If `load_offset()` returns `Ok(None)`, this method will attempt to `store_offset()` the
-    /// first delta it gets from the server. This lets you get an "accurate enough" timestamp
-    /// pretty quickly, instead of waiting for a full round of samples. Errors from that store are
-    /// ignored silently.
-    ///
-    /// If this returns `Ok(None)`, not enough samples were obtained to have enough confidence in
-    /// the result, likely because the `server_query()` method encountered an error for most tries.
-    /// Errors from `server_query()` are not returned, but instead are logged using tracing.
-    ///
-    /// Do not override.
-    ///
-    /// # Example
-    ///
-    /// ```ignore
-    /// loop {
-    ///     match simp.attempt_sync(Settings::default()).await {
-    ///         Err(err) => eprintln!("{err}"),
-    ///         Ok(None) => eprintln!("did not get enough samples to have confidence"),
-    ///         Ok(Some(offset)) => {
-    ///             println!("Obtained offset: {offset:?}");
-    ///             println!("The adjusted time is {}", simp.adjusted_timestamp().unwrap());
-    ///         }
-    ///     }
-    ///     sleep(Duration::from_secs(60));
-    /// }
-    /// ```
-    async fn attempt_sync(
-        &mut self,
-        settings: Settings,
-    ) -> Result<Option<SignedDuration>, Self::Err> {
-        let Settings { samples, jitter } = settings.clamp();
-        let current_offset = self.load_offset().await?.unwrap_or_default();
-        tracing::trace!(?samples, ?current_offset, "starting delta collection");
+mod futures;
 
-        let mut gap = Duration::ZERO;
-        let mut responses: Vec<Delta> = Vec::with_capacity(samples.into());
-        for _ in 0..settings.samples {
-            tracing::trace!(delay=?gap, max_jitter=?jitter, "sleeping to spread out requests");
-            Self::sleep(gap).await;
+mod history;
+pub use history::*;
 
-            // compute the next gap before we query, so if query_server errors we don't immediately reloop
-            gap = Duration::from_nanos(rand::random_range(
-                0..=u64::try_from(jitter.as_nanos()).unwrap(),
-            ));
-            // UNWRAP: jitter has been clamped to 0..=10 seconds, so nanos will never reach u64::MAX
+mod holdover;
+pub use holdover::*;
 
-            let response = match self
-                .query_server(Request {
-                    client: Timestamp::now(),
-                })
-                .await
-            {
-                Ok(response) => response,
-                Err(err) => {
-                    tracing::error!(?err, "query_server failed");
-                    continue;
-                }
-            };
+mod messages;
+pub use messages::*;
 
-            let Some(packet) = Delta::new(response, Timestamp::now()) else {
-                tracing::error!("local clock went backwards! skipping this sampling");
-                continue;
-            };
+mod monotonic;
+pub use monotonic::*;
 
-            tracing::trace!(latency=?packet.latency, delta=?packet.delta, "obtained raw offset from server");
-            responses.push(packet);
+mod multi;
+pub use multi::*;
 
-            if self.load_offset().await?.is_none() {
-                tracing::debug!(offset=?packet.delta, "no offset stored, storing initial delta");
-                let _ = self.store_offset(packet.delta).await;
-            }
-        }
+mod report;
+pub use report::*;
 
-        if !responses.is_empty() && responses.len() % 2 == 0 {
-            // if we have an even number of responses, we need to discard one
-            // the first response is most likely to be an outlier due to connection establishment
-            responses.remove(0);
-        }
+mod sampling;
 
-        if responses.len() < 3 {
-            tracing::debug!(
-                count = responses.len(),
-                "not enough responses for confidence"
-            );
-            return Ok(None);
-        }
+mod scheduler;
+pub use scheduler::*;
 
-        responses.sort_by_key(|r| r.latency);
-        let deltas = responses
-            .iter()
-            .map(|r| r.delta.as_millis_f64())
-            .collect::<Vec<_>>();
-        tracing::trace!(?deltas, "response deltas sorted by latency");
+mod server;
+pub use server::*;
 
-        let median_idx = deltas.len() / 2;
-        let median = deltas[median_idx];
+mod send;
+pub use send::*;
 
-        let mean: f64 = deltas.iter().copied().sum::<f64>() / deltas.len() as f64;
-        let variance: f64 = deltas
-            .iter()
-            .copied()
-            .map(|d| (d - mean).powi(2))
-            .sum::<f64>()
-            / ((deltas.len() - 1) as f64);
-        let stddev: f64 = variance.sqrt();
-        tracing::trace!(
-            ?median,
-            ?mean,
-            ?variance,
-            ?stddev,
-            "statistics about response deltas"
-        );
+mod session;
+pub use session::*;
 
-        let inliers = deltas
-            .iter()
-            .copied()
-            .filter(|d| *d >= median - stddev && *d <= median + stddev)
-            .collect::<Vec<_>>();
-        tracing::trace!(?inliers, "eliminated outliers");
+mod settings;
+pub use settings::*;
 
-        let offset = SignedDuration::from_micros(
-            ((inliers.iter().sum::<f64>() / (inliers.len() as f64)) * 1000.0) as i64,
-        );
+mod source;
+pub use source::*;
 
-        tracing::debug!(?offset, "storing calculated offset");
-        self.store_offset(offset).await?;
-        Ok(Some(offset))
-    }
-}
+mod synced;
+pub use synced::*;
@@ -135,7 +135,7 @@ mod tests {
         let request = Request {
             client: microround(Timestamp::from_str("2025-04-28T03:11:00.564184Z").unwrap()),
         };
-        let bytes = vec![0, 6, 51, 206, 8, 149, 148, 216];
+        let bytes = [0, 6, 51, 206, 8, 149, 148, 216];
         assert_eq!(request, Request::try_from(&bytes[..]).unwrap());
     }
 }
@@ -0,0 +1,157 @@
+use std::{
+    sync::{Mutex, PoisonError},
+    time::Duration,
+};
+
+use jiff::Timestamp;
+
+/// A guard that hands out non-decreasing timestamps.
+///
+/// Adjusted timestamps can go backwards: whenever a sync lowers the offset, or the system clock
+/// gets stepped. This is a problem if you use them to order events. Pass every timestamp through
+/// the same `Monotonic` (it can be shared between threads) and each one it hands out will be
+/// greater than or equal to all the ones it handed out before.
+///
+/// When a timestamp would go backwards, the previous timestamp is returned instead, and the
+/// amount it had to be clamped by is reported in [`MonotonicTimestamp::clamped`].
+///
+/// See also [`TimeSource::monotonic_timestamp()`](crate::TimeSource::monotonic_timestamp).
+#[derive(Debug, Default)]
+pub struct Monotonic {
+    last: Mutex<Option<Timestamp>>,
+}
+
+/// A timestamp obtained from a [`Monotonic`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct MonotonicTimestamp {
+    /// The non-decreasing timestamp.
+    pub timestamp: Timestamp,
+
+    /// How far back the input timestamp was from the previous one.
+    ///
+    /// This is zero if the input timestamp was used as-is.
+    pub clamped: Duration,
+}
+
+impl Monotonic {
+    /// Create a new guard, which has not seen any timestamp yet.
+    pub const fn new() -> Self {
+        Self {
+            last: Mutex::new(None),
+        }
+    }
+
+    /// Pass a timestamp through the guard.
+    ///
+    /// If the timestamp is before the last one handed out, the last one is returned again.
+    pub fn observe(&self, timestamp: Timestamp) -> MonotonicTimestamp {
+        let mut last = self.last.lock().unwrap_or_else(PoisonError::into_inner);
+        match *last {
+            Some(previous) if previous > timestamp => {
+                let clamped = previous.duration_since(timestamp).unsigned_abs();
+                tracing::debug!(
+                    ?previous,
+                    ?timestamp,
+                    ?clamped,
+                    "clamping timestamp that went backwards"
+                );
+                MonotonicTimestamp {
+                    timestamp: previous,
+                    clamped,
+                }
+            }
+            _ => {
+                *last = Some(timestamp);
+                MonotonicTimestamp {
+                    timestamp,
+                    clamped: Duration::ZERO,
+                }
+            }
+        }
+    }
+
+    /// The last timestamp handed out, if any.
+    pub fn last(&self) -> Option<Timestamp> {
+        *self.last.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{sync::Arc, thread};
+
+    use super::*;
+
+    #[test]
+    fn passes_increasing_timestamps() {
+        let mono = Monotonic::new();
+        let first = Timestamp::new(10, 0).unwrap();
+        let second = Timestamp::new(11, 0).unwrap();
+
+        assert_eq!(
+            mono.observe(first),
+            MonotonicTimestamp {
+                timestamp: first,
+                clamped: Duration::ZERO
+            }
+        );
+        assert_eq!(
+            mono.observe(second),
+            MonotonicTimestamp {
+                timestamp: second,
+                clamped: Duration::ZERO
+            }
+        );
+        assert_eq!(mono.last(), Some(second));
+    }
+
+    #[test]
+    fn equal_timestamps_are_not_clamped() {
+        let mono = Monotonic::new();
+        let ts = Timestamp::new(10, 0).unwrap();
+
+        mono.observe(ts);
+        assert_eq!(mono.observe(ts).clamped, Duration::ZERO);
+    }
+
+    #[test]
+    fn clamps_backwards_timestamps() {
+        let mono = Monotonic::new();
+        let first = Timestamp::new(10, 0).unwrap();
+        let backwards = Timestamp::new(9, 500_000_000).unwrap();
+
+        mono.observe(first);
+        assert_eq!(
+            mono.observe(backwards),
+            MonotonicTimestamp {
+                timestamp: first,
+                clamped: Duration::from_millis(500)
+            }
+        );
+        assert_eq!(mono.last(), Some(first));
+    }
+
+    #[test]
+    fn across_threads() {
+        let mono = Arc::new(Monotonic::new());
+        let handles = (0..4)
+            .map(|_| {
+                let mono = mono.clone();
+                thread::spawn(move || {
+                    let mut previous = None;
+                    for _ in 0..1000 {
+                        let ts = mono.observe(Timestamp::now()).timestamp;
+                        if let Some(previous) = previous {
+                            assert!(ts >= previous, "{ts} < {previous}");
+                        }
+                        previous = Some(ts);
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+
+        for handle in handles {
+            handle.join().unwrap();
+        }
+    }
+}
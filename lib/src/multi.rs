@@ -0,0 +1,313 @@
+use std::{mem, time::Duration};
+
+use jiff::SignedDuration;
+
+use crate::{Estimate, Request, Response, Settings, SyncError, TimesimpClient, holdover, sampling};
+
+/// A time sync client that samples several servers.
+///
+/// A single misconfigured server can shift every client that syncs against it. Implement this on
+/// top of [`TimesimpClient`] to instead sample several servers, and only trust those that agree
+/// with the majority.
+///
+/// Servers are identified by their index, from `0` to `servers() - 1`. The
+/// [`query_server()`](TimesimpClient::query_server) method is not used by multi-server syncs; it's
+/// still used by [`attempt_sync()`](TimesimpClient::attempt_sync) and may simply query one of the
+/// servers.
+#[allow(async_fn_in_trait)]
+pub trait MultiTimesimp: TimesimpClient {
+    /// How many servers are available.
+    fn servers(&self) -> usize;
+
+    /// Query the timesimp server with the given index.
+    ///
+    /// This has the same requirements as [`query_server()`](TimesimpClient::query_server).
+    async fn query_server_at(&self, server: usize, request: Request)
+    -> Result<Response, Self::Err>;
+
+    /// The multi-server client state driver. Call this in a loop.
+    ///
+    /// Do not override.
+    ///
+    /// Each server is sampled in turn, as [`attempt_sync()`](TimesimpClient::attempt_sync) would,
+    /// to get an offset and a confidence interval around it. The `settings` apply to each server's
+    /// round separately; in particular, the deadline is per server. The intervals are then combined
+    /// with Marzullo's algorithm (as used in NTP's selection): servers whose interval contains the
+    /// region where the most intervals overlap are truechimers, the others are falsetickers and are
+    /// rejected. If the truechimers are a majority of all the servers, including those that
+    /// couldn't be sampled, the mean of their offsets is stored and returned. Otherwise, the
+    /// attempt fails with [`SyncError::NoMajority`], so that with most servers down, the few that
+    /// remain can't set the offset on their own.
+    ///
+    /// Unlike `attempt_sync()`, this never stores the first delta it gets when there's no offset
+    /// stored yet, as it can't know whether it comes from a falseticker.
+    ///
+    /// As with `attempt_sync()`, errors from `query_server_at()` are logged, and given back in the
+    /// [`SyncError`] if the attempt fails; servers that didn't give enough samples are reported as
+    /// unreachable. If the clock went backwards while sampling, the attempt fails as it would with
+    /// `attempt_sync()`. The sample [`history`](Settings::history) is not used. The combined offset
+    /// is checked against the limits set in the [`Settings`] in the same way.
+    async fn attempt_multi_sync(
+        &mut self,
+        settings: Settings,
+    ) -> Result<MultiSync, SyncError<Self::Err>> {
+        let current_offset = self.load_offset().await.map_err(SyncError::Storage)?;
+        let servers = self.servers();
+        let mut estimates = Vec::with_capacity(servers);
+        let mut errors = Vec::with_capacity(servers);
+        let mut unreachable = Vec::new();
+        for server in 0..servers {
+            tracing::trace!(?server, "sampling server");
+            let mut samples = sampling::collect(self, settings, false, async |simp, request| {
+                simp.query_server_at(server, request).await
+            })
+            .await
+            .map_err(SyncError::Storage)?;
+
+            errors.push(mem::take(&mut samples.errors));
+            if let Err(err @ SyncError::ClockWentBackwards { .. }) = samples.check() {
+                holdover::record_failure(self)
+                    .await
+                    .map_err(SyncError::Storage)?;
+                return Err(err);
+            }
+
+            match Estimate::new(samples.deltas, settings.outliers) {
+                Some(estimate) => estimates.push(ServerEstimate {
+                    server,
+                    offset: estimate.offset,
+                    error: estimate.error(),
+                }),
+                None => {
+                    tracing::debug!(?server, "not enough samples, server is unreachable");
+                    unreachable.push(server);
+                }
+            }
+        }
+
+        let (truechimers, falsetickers) = select(estimates, servers);
+        tracing::debug!(
+            ?truechimers,
+            ?falsetickers,
+            ?unreachable,
+            "selected servers"
+        );
+
+        if truechimers.is_empty() {
+            tracing::debug!("no majority of servers agree");
+            holdover::record_failure(self)
+                .await
+                .map_err(SyncError::Storage)?;
+            return Err(SyncError::NoMajority {
+                servers,
+                falsetickers,
+                unreachable,
+                errors,
+            });
+        }
+
+        let total: i128 = truechimers.iter().map(|est| est.offset.as_nanos()).sum();
+        let offset = SignedDuration::from_nanos((total / truechimers.len() as i128) as i64);
+        if let Err(err) = settings.check_offset(current_offset, offset) {
+            holdover::record_failure(self)
+                .await
+                .map_err(SyncError::Storage)?;
+            return Err(err);
+        }
+
+        tracing::debug!(?offset, "storing combined offset");
+        self.store_offset(offset)
+            .await
+            .map_err(SyncError::Storage)?;
+
+        // the combined offset is within the error of every truechimer
+        let error = truechimers
+            .iter()
+            .map(|est| (est.offset - offset).unsigned_abs() + est.error)
+            .max()
+            .unwrap_or_default();
+        holdover::record_sync(self, offset, error)
+            .await
+            .map_err(SyncError::Storage)?;
+
+        Ok(MultiSync {
+            offset,
+            truechimers,
+            falsetickers,
+            unreachable,
+        })
+    }
+}
+
+/// The offset estimated from a single server during a multi-server sync.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ServerEstimate {
+    /// The index of the server.
+    pub server: usize,
+
+    /// The offset estimated from this server's samples.
+    pub offset: SignedDuration,
+
+    /// The maximum error of the offset.
+    ///
+    /// This is the lowest one-way latency seen plus the standard deviation of the samples; the
+    /// server's confidence interval is `offset ± error`.
+    pub error: Duration,
+}
+
+/// The result of a successful multi-server sync.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct MultiSync {
+    /// The combined offset, which has been stored.
+    pub offset: SignedDuration,
+
+    /// The servers that agreed with the majority, and were used for the combined offset.
+    pub truechimers: Vec<ServerEstimate>,
+
+    /// The servers that disagreed with the majority, and were rejected.
+    pub falsetickers: Vec<ServerEstimate>,
+
+    /// The servers that didn't give enough samples to have confidence in their offset.
+    pub unreachable: Vec<usize>,
+}
+
+/// Split estimates into truechimers and falsetickers, using Marzullo's algorithm.
+///
+/// Finds the region covered by the most confidence intervals; the estimates whose interval
+/// contains it are the truechimers. If they're not a strict majority of all `servers`, including
+/// those that gave no estimate, there are no truechimers.
+fn select(
+    estimates: Vec<ServerEstimate>,
+    servers: usize,
+) -> (Vec<ServerEstimate>, Vec<ServerEstimate>) {
+    let interval = |est: &ServerEstimate| {
+        let offset = est.offset.as_nanos();
+        let error = est.error.as_nanos() as i128;
+        (offset - error, offset + error)
+    };
+
+    // starts sort before ends at the same point, so that touching intervals overlap
+    let mut edges = estimates
+        .iter()
+        .flat_map(|est| {
+            let (low, high) = interval(est);
+            [(low, false), (high, true)]
+        })
+        .collect::<Vec<_>>();
+    edges.sort();
+
+    let mut count = 0;
+    let mut best = 0;
+    let mut best_start = 0;
+    for (point, is_end) in edges {
+        if is_end {
+            count -= 1;
+        } else {
+            count += 1;
+            if count > best {
+                best = count;
+                best_start = point;
+            }
+        }
+    }
+
+    if best * 2 <= servers {
+        return (Vec::new(), estimates);
+    }
+
+    // an interval containing the start of the best region also contains the whole of it, as the
+    // set of covering intervals only changes at edges
+    estimates.into_iter().partition(|est| {
+        let (low, high) = interval(est);
+        low <= best_start && best_start <= high
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn est(server: usize, offset_ms: i64, error_ms: u64) -> ServerEstimate {
+        ServerEstimate {
+            server,
+            offset: SignedDuration::from_millis(offset_ms),
+            error: Duration::from_millis(error_ms),
+        }
+    }
+
+    fn servers(list: &[ServerEstimate]) -> Vec<usize> {
+        list.iter().map(|est| est.server).collect()
+    }
+
+    #[test]
+    fn single_server() {
+        let (truechimers, falsetickers) = select(vec![est(0, 100, 5)], 1);
+        assert_eq!(servers(&truechimers), vec![0]);
+        assert_eq!(servers(&falsetickers), Vec::<usize>::new());
+    }
+
+    #[test]
+    fn all_agree() {
+        let (truechimers, falsetickers) =
+            select(vec![est(0, 100, 5), est(1, 103, 5), est(2, 98, 5)], 3);
+        assert_eq!(servers(&truechimers), vec![0, 1, 2]);
+        assert_eq!(servers(&falsetickers), Vec::<usize>::new());
+    }
+
+    #[test]
+    fn touching_intervals_agree() {
+        let (truechimers, _) = select(vec![est(0, 100, 5), est(1, 110, 5)], 2);
+        assert_eq!(servers(&truechimers), vec![0, 1]);
+    }
+
+    #[test]
+    fn rejects_falseticker() {
+        let (truechimers, falsetickers) = select(
+            vec![est(0, 100, 5), est(1, -1_000_000_000, 5), est(2, 103, 5)],
+            3,
+        );
+        assert_eq!(servers(&truechimers), vec![0, 2]);
+        assert_eq!(servers(&falsetickers), vec![1]);
+    }
+
+    #[test]
+    fn no_majority() {
+        let (truechimers, falsetickers) = select(vec![est(0, 100, 5), est(1, 200, 5)], 2);
+        assert_eq!(servers(&truechimers), Vec::<usize>::new());
+        assert_eq!(servers(&falsetickers), vec![0, 1]);
+    }
+
+    #[test]
+    fn majority_of_all_servers() {
+        // the two other servers gave no estimate
+        let (truechimers, falsetickers) = select(vec![est(0, 100, 5)], 3);
+        assert_eq!(servers(&truechimers), Vec::<usize>::new());
+        assert_eq!(servers(&falsetickers), vec![0]);
+
+        let (truechimers, falsetickers) = select(vec![est(0, 100, 5), est(1, 103, 5)], 3);
+        assert_eq!(servers(&truechimers), vec![0, 1]);
+        assert_eq!(servers(&falsetickers), Vec::<usize>::new());
+    }
+
+    #[test]
+    fn wide_interval_agrees_with_both_sides() {
+        let (truechimers, falsetickers) = select(
+            vec![
+                est(0, 100, 5),
+                est(1, 150, 100),
+                est(2, 200, 5),
+                est(3, 102, 5),
+            ],
+            4,
+        );
+        assert_eq!(servers(&truechimers), vec![0, 1, 3]);
+        assert_eq!(servers(&falsetickers), vec![2]);
+    }
+
+    #[test]
+    fn nothing() {
+        let (truechimers, falsetickers) = select(Vec::new(), 0);
+        assert!(truechimers.is_empty());
+        assert!(falsetickers.is_empty());
+    }
+}
@@ -0,0 +1,36 @@
+use std::time::Duration;
+
+use jiff::SignedDuration;
+
+/// The result of a successful sync attempt.
+///
+/// Obtained from
+/// [`TimesimpClient::attempt_sync_report()`](crate::TimesimpClient::attempt_sync_report), or at the
+/// end of a [`SyncSession`](crate::SyncSession).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SyncReport {
+    /// The calculated offset.
+    ///
+    /// If the report is from a sync method, it has been stored; from a
+    /// [`SyncSession`](crate::SyncSession), storing it is up to you.
+    pub offset: SignedDuration,
+
+    /// The maximum error of the calculated offset.
+    ///
+    /// See [`SyncRecord::error`](crate::SyncRecord).
+    pub error: Duration,
+
+    /// How many samples were obtained successfully.
+    pub samples: usize,
+
+    /// How many samples failed.
+    ///
+    /// This includes errors from `query_server()` and timeouts.
+    pub failures: usize,
+
+    /// How many of the failed samples timed out.
+    pub timeouts: usize,
+
+    /// Whether the attempt was cut short by its deadline.
+    pub deadline_reached: bool,
+}
@@ -0,0 +1,424 @@
+use std::{convert::Infallible, future::pending, mem, time::Duration};
+
+use jiff::Timestamp;
+use rand::{Rng as _, SeedableRng as _, rngs::StdRng};
+
+use crate::{
+    Action, Adaptive, Clock, Delta, Estimate, OutlierFilter, QueryId, Request, Response, Settings,
+    Spacing, SyncError, SyncReport, SyncSession, TimesimpClient, attempt,
+    futures::{Either, Unordered, race},
+};
+
+/// The samples gathered during a round.
+#[derive(Debug, Clone)]
+pub(crate) struct Samples<E = Infallible> {
+    /// The deltas obtained successfully.
+    pub(crate) deltas: Vec<Delta>,
+
+    /// How many samples failed, for any reason.
+    pub(crate) failures: usize,
+
+    /// How many samples failed because they timed out.
+    pub(crate) timeouts: usize,
+
+    /// Whether the round was cut short by the deadline.
+    pub(crate) deadline_reached: bool,
+
+    /// The errors from the queries that failed.
+    pub(crate) errors: Vec<E>,
+
+    /// The latest timestamp a request was made at.
+    last_request: Option<Timestamp>,
+
+    /// How far the clock went backwards during the round, if it did.
+    backwards: Option<Duration>,
+}
+
+impl<E> Samples<E> {
+    pub(crate) fn with_capacity(capacity: u8) -> Self {
+        Self {
+            deltas: Vec::with_capacity(capacity.into()),
+            failures: 0,
+            timeouts: 0,
+            deadline_reached: false,
+            errors: Vec::new(),
+            last_request: None,
+            backwards: None,
+        }
+    }
+
+    /// Make a request stamped with the current time, noticing if the clock went backwards.
+    pub(crate) fn request(&mut self, clock: &dyn Clock) -> Request {
+        let client = clock.now();
+        if let Some(last) = self.last_request
+            && client < last
+        {
+            let by = last.duration_since(client).unsigned_abs();
+            tracing::warn!(?by, "clock went backwards during the round");
+            self.backwards = self.backwards.max(Some(by));
+        }
+
+        self.last_request = self.last_request.max(Some(client));
+        Request { client }
+    }
+
+    /// Check that the round may give an estimate.
+    ///
+    /// It can't if the clock went backwards during the round, as deltas from before and after
+    /// are relative to different clocks; or if every query failed before the deadline.
+    pub(crate) fn check(&mut self) -> Result<(), SyncError<E>> {
+        if let Some(by) = self.backwards {
+            return Err(SyncError::ClockWentBackwards { by });
+        }
+
+        if self.deltas.is_empty() && self.failures > 0 && !self.deadline_reached {
+            return Err(SyncError::AllQueriesFailed {
+                errors: mem::take(&mut self.errors),
+                timeouts: self.timeouts,
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Estimate the offset from the given deltas, and report on the round.
+    ///
+    /// The deltas are those of the round, or a window of the history including them.
+    pub(crate) fn conclude(
+        self,
+        deltas: Vec<Delta>,
+        outliers: OutlierFilter,
+    ) -> Result<(Estimate, SyncReport), SyncError<E>> {
+        let count = deltas.len();
+        let Some(estimate) = Estimate::new(deltas, outliers) else {
+            return Err(SyncError::TooFewInliers {
+                samples: count,
+                errors: self.errors,
+                timeouts: self.timeouts,
+                deadline_reached: self.deadline_reached,
+            });
+        };
+
+        let report = SyncReport {
+            offset: estimate.offset,
+            error: estimate.error(),
+            samples: self.deltas.len(),
+            failures: self.failures,
+            timeouts: self.timeouts,
+            deadline_reached: self.deadline_reached,
+        };
+        Ok((estimate, report))
+    }
+
+    /// Whether adaptive sampling can stop, as the estimate is precise enough.
+    pub(crate) fn precise_enough(
+        &self,
+        samples: u8,
+        adaptive: Option<Adaptive>,
+        outliers: OutlierFilter,
+    ) -> bool {
+        let Some(adaptive) = adaptive else {
+            return false;
+        };
+
+        if self.deltas.len() < samples.into() {
+            return false;
+        }
+
+        let Some(estimate) = Estimate::new(self.deltas.clone(), outliers) else {
+            return false;
+        };
+
+        let error = estimate.standard_error();
+        if error.is_some_and(|error| error <= adaptive.target) {
+            tracing::debug!(
+                count = self.deltas.len(),
+                ?error,
+                "estimate is precise enough, stopping"
+            );
+            true
+        } else {
+            tracing::trace!(
+                count = self.deltas.len(),
+                ?error,
+                "estimate is not yet precise enough"
+            );
+            false
+        }
+    }
+}
+
+impl Samples {
+    /// Attach the errors from the queries that failed, which a [`SyncSession`] doesn't know about.
+    pub(crate) fn with_errors<E>(self, errors: Vec<E>) -> Samples<E> {
+        Samples {
+            deltas: self.deltas,
+            failures: self.failures,
+            timeouts: self.timeouts,
+            deadline_reached: self.deadline_reached,
+            errors,
+            last_request: self.last_request,
+            backwards: self.backwards,
+        }
+    }
+}
+
+/// The generator for the random gaps between samples.
+pub(crate) fn gap_rng(seed: Option<u64>) -> StdRng {
+    seed.map_or_else(StdRng::from_os_rng, StdRng::seed_from_u64)
+}
+
+/// A random duration up to a maximum, uniformly distributed.
+fn uniform(rng: &mut StdRng, min: Duration, max: Duration) -> Duration {
+    // UNWRAP: durations here are clamped to at most a minute, so nanos will never reach u64::MAX
+    let nanos = |duration: Duration| u64::try_from(duration.as_nanos()).unwrap();
+    Duration::from_nanos(rng.random_range(nanos(min.min(max))..=nanos(max)))
+}
+
+/// A random gap between two samples.
+pub(crate) fn random_gap(rng: &mut StdRng, jitter: Duration, spacing: Spacing) -> Duration {
+    match spacing {
+        Spacing::Uniform => uniform(rng, Duration::ZERO, jitter),
+        Spacing::Exponential => {
+            let mean = jitter.as_secs_f64() / 2.0;
+            let gap = -(1.0 - rng.random::<f64>()).ln() * mean;
+            Duration::from_secs_f64(gap).min(jitter * 5)
+        }
+        Spacing::Fixed { spread } => {
+            let spread = spread.min(jitter);
+            uniform(rng, jitter - spread, jitter + spread)
+        }
+        Spacing::MinGap(min) => uniform(rng, min, jitter),
+    }
+}
+
+/// A random delay before the first sample.
+pub(crate) fn initial_gap(rng: &mut StdRng, initial_delay: Option<Duration>) -> Duration {
+    initial_delay.map_or(Duration::ZERO, |max| uniform(rng, Duration::ZERO, max))
+}
+
+/// Gather a round of samples from a server.
+///
+/// This drives a [`SyncSession`], which decides when to send queries and when the round is over,
+/// following the settings. `query` is called with the client and the request to send, which lets
+/// the caller pick which server to query without holding a borrow on it across the whole round.
+///
+/// If a timeout is set, each query is raced against `simp.sleep()`, and abandoned if that finishes
+/// first. Queries the session gives up on, because they timed out or the session is over, are
+/// dropped straight away, so they don't hold a place against [`Settings::in_flight`].
+///
+/// If `store_initial` is true and no offset is stored yet, the first delta obtained is stored as
+/// the offset as soon as no queries are in flight, unless it's outside the limits in the settings.
+pub(crate) async fn collect<T: TimesimpClient + ?Sized>(
+    simp: &mut T,
+    settings: Settings,
+    store_initial: bool,
+    query: impl AsyncFn(&T, Request) -> Result<Response, T::Err>,
+) -> Result<Samples<T::Err>, T::Err> {
+    let settings = settings.clamp();
+    let mut session = SyncSession::new(settings, simp.clock());
+    let mut errors = Vec::new();
+    let mut store_initial = store_initial;
+    loop {
+        let over = drive(
+            &*simp,
+            &mut session,
+            &mut errors,
+            &query,
+            settings.timeout,
+            store_initial,
+        )
+        .await;
+
+        if store_initial && let Some(packet) = session.round().deltas.first().copied() {
+            store_initial = false;
+            store_initial_delta(simp, &settings, packet).await?;
+        }
+
+        if over {
+            return Ok(session.into_round().with_errors(errors));
+        }
+    }
+}
+
+/// Drive a session, sending its queries and sleeping as it says.
+///
+/// Returns true when the session is over. If `until_delta` is true, this also returns, with
+/// false, once a delta has been obtained and no queries are in flight.
+async fn drive<T: TimesimpClient + ?Sized>(
+    simp: &T,
+    session: &mut SyncSession,
+    errors: &mut Vec<T::Err>,
+    query: &impl AsyncFn(&T, Request) -> Result<Response, T::Err>,
+    timeout: Option<Duration>,
+    until_delta: bool,
+) -> bool {
+    let mut in_flight = Unordered::new();
+    loop {
+        let action = session.poll(simp.clock());
+
+        // drop the queries the session abandoned, so they don't count against `in_flight`
+        in_flight.retain(|id| session.is_pending(*id));
+
+        let wake = match action {
+            Action::Send(id, request) => {
+                tracing::trace!(in_flight = in_flight.len(), "sending query");
+                in_flight.push(id, send(simp, query, id, request, timeout));
+                continue;
+            }
+            Action::Wait(wake) => wake,
+            Action::Done(_) => return true,
+        };
+
+        let wait = async {
+            match wake {
+                Some(wake) => {
+                    let now = simp.clock().instant();
+                    simp.sleep(wake.saturating_duration_since(now)).await;
+                }
+                None => pending().await,
+            }
+        };
+
+        match race(in_flight.next(), wait).await {
+            Either::Left((id, Some(Ok(response)))) => {
+                session.receive(id, response, simp.clock().instant());
+            }
+            Either::Left((id, Some(Err(err)))) => {
+                if session.is_pending(id) {
+                    session.fail(id, simp.clock().instant());
+                    errors.push(err);
+                }
+            }
+            // the session counts the timeout once it's due
+            Either::Left((_, None)) => {}
+            Either::Right(()) => {}
+        }
+
+        if until_delta && in_flight.is_empty() && !session.round().deltas.is_empty() {
+            return false;
+        }
+    }
+}
+
+/// Send a single query, abandoning it if it takes longer than the timeout.
+///
+/// Returns `None` if it was abandoned.
+async fn send<T: TimesimpClient + ?Sized>(
+    simp: &T,
+    query: &impl AsyncFn(&T, Request) -> Result<Response, T::Err>,
+    id: QueryId,
+    request: Request,
+    timeout: Option<Duration>,
+) -> (QueryId, Option<Result<Response, T::Err>>) {
+    let result = match timeout {
+        None => Some(query(simp, request).await),
+        Some(timeout) => match race(query(simp, request), simp.sleep(timeout)).await {
+            Either::Left(result) => Some(result),
+            Either::Right(()) => None,
+        },
+    };
+
+    if let Some(Err(err)) = &result {
+        tracing::error!(?err, "query_server failed");
+    }
+
+    (id, result)
+}
+
+/// Store a delta as the offset, if none is stored yet and it's within the limits.
+async fn store_initial_delta<T: TimesimpClient + ?Sized>(
+    simp: &mut T,
+    settings: &Settings,
+    packet: Delta,
+) -> Result<(), T::Err> {
+    let current = simp.load_offset().await?;
+    match attempt::initial_offset(settings, current, packet) {
+        Some(offset) => simp.store_offset(offset).await,
+        None => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn gaps(seed: Option<u64>, spacing: Spacing) -> Vec<Duration> {
+        let mut rng = gap_rng(seed);
+        (0..1000)
+            .map(|_| random_gap(&mut rng, Duration::from_secs(2), spacing))
+            .collect()
+    }
+
+    fn mean(gaps: &[Duration]) -> Duration {
+        gaps.iter().sum::<Duration>() / gaps.len() as u32
+    }
+
+    #[test]
+    fn seeded_gaps_are_reproducible() {
+        assert_eq!(
+            gaps(Some(42), Spacing::Uniform),
+            gaps(Some(42), Spacing::Uniform)
+        );
+        assert_ne!(
+            gaps(Some(42), Spacing::Uniform),
+            gaps(Some(43), Spacing::Uniform)
+        );
+    }
+
+    #[test]
+    fn uniform_gaps() {
+        let gaps = gaps(Some(1), Spacing::Uniform);
+        assert!(gaps.iter().all(|gap| *gap <= Duration::from_secs(2)));
+        let mean = mean(&gaps);
+        assert!(
+            mean > Duration::from_millis(900) && mean < Duration::from_millis(1100),
+            "mean = {mean:?}"
+        );
+    }
+
+    #[test]
+    fn exponential_gaps() {
+        let gaps = gaps(Some(1), Spacing::Exponential);
+        assert!(gaps.iter().all(|gap| *gap <= Duration::from_secs(10)));
+        assert!(gaps.iter().any(|gap| *gap > Duration::from_secs(2)));
+        let mean = mean(&gaps);
+        assert!(
+            mean > Duration::from_millis(900) && mean < Duration::from_millis(1100),
+            "mean = {mean:?}"
+        );
+    }
+
+    #[test]
+    fn fixed_gaps() {
+        let gaps = gaps(
+            Some(1),
+            Spacing::Fixed {
+                spread: Duration::from_millis(100),
+            },
+        );
+        assert!(
+            gaps.iter()
+                .all(|gap| *gap >= Duration::from_millis(1900)
+                    && *gap <= Duration::from_millis(2100))
+        );
+    }
+
+    #[test]
+    fn min_gaps() {
+        let gaps = gaps(Some(1), Spacing::MinGap(Duration::from_millis(1500)));
+        assert!(
+            gaps.iter()
+                .all(|gap| *gap >= Duration::from_millis(1500) && *gap <= Duration::from_secs(2))
+        );
+    }
+
+    #[test]
+    fn initial_delay() {
+        let mut rng = gap_rng(Some(1));
+        assert_eq!(initial_gap(&mut rng, None), Duration::ZERO);
+        for _ in 0..100 {
+            assert!(initial_gap(&mut rng, Some(Duration::from_secs(1))) <= Duration::from_secs(1));
+        }
+    }
+}
@@ -0,0 +1,236 @@
+use std::time::Duration;
+
+use jiff::SignedDuration;
+
+/// Settings for a [`Scheduler`].
+///
+/// Values set will be clamped to acceptable ones before use.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SchedulerSettings {
+    /// The shortest interval between two sync attempts.
+    ///
+    /// Used while converging and to retry after a failure. Minimum 1s, default 16s.
+    pub min_interval: Duration,
+
+    /// The longest interval between two sync attempts.
+    ///
+    /// Must be at least `min_interval`, default 1024s (about 17 minutes).
+    pub max_interval: Duration,
+
+    /// How much the offset may change between two syncs for them to be considered stable.
+    ///
+    /// Default 10ms.
+    pub threshold: Duration,
+
+    /// How many stable syncs in a row are needed before backing off.
+    ///
+    /// Minimum 1, default 3.
+    pub stable_syncs: u8,
+}
+
+impl Default for SchedulerSettings {
+    fn default() -> Self {
+        Self {
+            min_interval: Duration::from_secs(16),
+            max_interval: Duration::from_secs(1024),
+            threshold: Duration::from_millis(10),
+            stable_syncs: 3,
+        }
+    }
+}
+
+impl SchedulerSettings {
+    /// Clamp to acceptable values.
+    pub(crate) fn clamp(self) -> Self {
+        let min_interval = self.min_interval.max(Duration::from_secs(1));
+        Self {
+            min_interval,
+            max_interval: self.max_interval.max(min_interval),
+            threshold: self.threshold,
+            stable_syncs: self.stable_syncs.max(1),
+        }
+    }
+}
+
+/// Picks the interval between sync attempts from the observed stability.
+///
+/// This is similar to NTP's poll adjustment. The scheduler starts at the minimum interval, so the
+/// first syncs happen quickly while the offset converges. Each time enough syncs in a row are
+/// stable (the offset barely changed), the interval doubles, up to the maximum. When the offset
+/// changes by more than the threshold, the interval halves. When a sync fails, the next attempt
+/// is made at the minimum interval, doubling for every further failure in a row.
+///
+/// As with any schedule, sleep for the intervals on your raw system monotonic clock or equivalent
+/// (for example `tokio::time::sleep`), not on adjusted time.
+///
+/// # Example
+///
+/// ```ignore
+/// let mut scheduler = Scheduler::default();
+/// loop {
+///     let offset = simp.attempt_sync(Settings::default()).await.ok();
+///     tokio::time::sleep(scheduler.update(offset)).await;
+/// }
+/// ```
+#[derive(Debug, Clone)]
+pub struct Scheduler {
+    settings: SchedulerSettings,
+    interval: Duration,
+    last_offset: Option<SignedDuration>,
+    stable: u8,
+    failures: u32,
+}
+
+impl Default for Scheduler {
+    fn default() -> Self {
+        Self::new(SchedulerSettings::default())
+    }
+}
+
+impl Scheduler {
+    /// Create a new scheduler.
+    pub fn new(settings: SchedulerSettings) -> Self {
+        let settings = settings.clamp();
+        Self {
+            settings,
+            interval: settings.min_interval,
+            last_offset: None,
+            stable: 0,
+            failures: 0,
+        }
+    }
+
+    /// The current interval between sync attempts, when they succeed.
+    pub fn interval(&self) -> Duration {
+        self.interval
+    }
+
+    /// Record the outcome of a sync attempt, and get the interval until the next one.
+    ///
+    /// Pass the offset obtained, or `None` if the attempt failed for any reason.
+    pub fn update(&mut self, offset: Option<SignedDuration>) -> Duration {
+        let SchedulerSettings {
+            min_interval,
+            max_interval,
+            threshold,
+            stable_syncs,
+        } = self.settings;
+
+        let Some(offset) = offset else {
+            let backoff = min_interval
+                .saturating_mul(2_u32.saturating_pow(self.failures))
+                .min(max_interval);
+            self.failures = self.failures.saturating_add(1);
+            tracing::debug!(failures=?self.failures, ?backoff, "sync failed, retrying soon");
+            return backoff;
+        };
+        self.failures = 0;
+
+        let change = self.last_offset.map(|last| (offset - last).unsigned_abs());
+        self.last_offset = Some(offset);
+
+        match change {
+            Some(change) if change <= threshold => {
+                self.stable += 1;
+                if self.stable >= stable_syncs {
+                    self.stable = 0;
+                    self.interval = self.interval.saturating_mul(2).min(max_interval);
+                    tracing::debug!(
+                        ?change,
+                        interval = ?self.interval,
+                        "offset is stable, backing off"
+                    );
+                }
+            }
+            _ => {
+                self.stable = 0;
+                self.interval = (self.interval / 2).max(min_interval);
+                tracing::debug!(
+                    ?change,
+                    interval = ?self.interval,
+                    "offset is changing, polling faster"
+                );
+            }
+        }
+
+        self.interval
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn settings() -> SchedulerSettings {
+        SchedulerSettings {
+            min_interval: Duration::from_secs(10),
+            max_interval: Duration::from_secs(80),
+            threshold: Duration::from_millis(5),
+            stable_syncs: 2,
+        }
+    }
+
+    fn ms(ms: i64) -> Option<SignedDuration> {
+        Some(SignedDuration::from_millis(ms))
+    }
+
+    #[test]
+    fn starts_at_minimum() {
+        let mut scheduler = Scheduler::new(settings());
+        assert_eq!(scheduler.interval(), Duration::from_secs(10));
+        assert_eq!(scheduler.update(ms(100)), Duration::from_secs(10));
+    }
+
+    #[test]
+    fn backs_off_when_stable() {
+        let mut scheduler = Scheduler::new(settings());
+        assert_eq!(scheduler.update(ms(100)), Duration::from_secs(10));
+        assert_eq!(scheduler.update(ms(101)), Duration::from_secs(10));
+        assert_eq!(scheduler.update(ms(100)), Duration::from_secs(20));
+        assert_eq!(scheduler.update(ms(102)), Duration::from_secs(20));
+        assert_eq!(scheduler.update(ms(103)), Duration::from_secs(40));
+        assert_eq!(scheduler.update(ms(103)), Duration::from_secs(40));
+        assert_eq!(scheduler.update(ms(103)), Duration::from_secs(80));
+        assert_eq!(scheduler.update(ms(103)), Duration::from_secs(80));
+        assert_eq!(scheduler.update(ms(103)), Duration::from_secs(80));
+    }
+
+    #[test]
+    fn polls_faster_when_unstable() {
+        let mut scheduler = Scheduler::new(settings());
+        for offset in [100, 100, 100, 100, 100] {
+            scheduler.update(ms(offset));
+        }
+        assert_eq!(scheduler.interval(), Duration::from_secs(40));
+        assert_eq!(scheduler.update(ms(200)), Duration::from_secs(20));
+        assert_eq!(scheduler.update(ms(300)), Duration::from_secs(10));
+        assert_eq!(scheduler.update(ms(400)), Duration::from_secs(10));
+    }
+
+    #[test]
+    fn failures_back_off_from_minimum() {
+        let mut scheduler = Scheduler::new(settings());
+        for offset in [100, 100, 100, 100, 100] {
+            scheduler.update(ms(offset));
+        }
+        assert_eq!(scheduler.update(None), Duration::from_secs(10));
+        assert_eq!(scheduler.update(None), Duration::from_secs(20));
+        assert_eq!(scheduler.update(None), Duration::from_secs(40));
+        assert_eq!(scheduler.update(None), Duration::from_secs(80));
+        assert_eq!(scheduler.update(None), Duration::from_secs(80));
+
+        // the stable interval is resumed after a successful sync
+        assert_eq!(scheduler.update(ms(100)), Duration::from_secs(40));
+    }
+
+    #[test]
+    fn clamps_settings() {
+        let scheduler = Scheduler::new(SchedulerSettings {
+            min_interval: Duration::ZERO,
+            max_interval: Duration::ZERO,
+            threshold: Duration::ZERO,
+            stable_syncs: 0,
+        });
+        assert_eq!(scheduler.interval(), Duration::from_secs(1));
+    }
+}
@@ -0,0 +1,319 @@
+use std::time::Duration;
+
+use jiff::{SignedDuration, Timestamp};
+
+use crate::{
+    Clock, Monotonic, MonotonicTimestamp, Request, Response, Sample, Settings, SyncError,
+    SyncRecord, SyncReport, SyncState, SyncedClock, SystemClock, TimeBounds, TimeSource,
+    TimesimpClient, TimesimpServer,
+};
+
+/// A source of adjusted time, with `Send` futures.
+///
+/// This is the same as [`TimeSource`], but its futures are declared `Send`, so it can be used in
+/// generic code that spawns them on a multi-threaded runtime. Implement this instead of
+/// `TimeSource`, which is then implemented for you.
+///
+/// The [`Err`](SendTimeSource::Err) type must be `Send`, and implementations `Send + Sync`.
+///
+/// The provided methods are available here suffixed with `_send`, returning `Send` futures, so
+/// they don't clash with those of `TimeSource` when both traits are in scope. The required methods
+/// have the same names in both traits: if both are in scope, call them through one, such as
+/// `SendTimeSource::load_offset(&source)`.
+pub trait SendTimeSource: Send + Sync {
+    /// Error for your required methods.
+    type Err: std::error::Error + Send;
+
+    /// Load the current time offset.
+    ///
+    /// See [`TimeSource::load_offset()`].
+    fn load_offset(&self)
+    -> impl Future<Output = Result<Option<SignedDuration>, Self::Err>> + Send;
+
+    /// The clock to read the time from.
+    ///
+    /// Optional: see [`TimeSource::clock()`].
+    fn clock(&self) -> &dyn Clock {
+        &SystemClock
+    }
+
+    /// Load the record of the last successful sync.
+    ///
+    /// Optional: see [`TimeSource::load_sync_record()`].
+    fn load_sync_record(
+        &self,
+    ) -> impl Future<Output = Result<Option<SyncRecord>, Self::Err>> + Send {
+        async { Ok(None) }
+    }
+
+    /// Obtain an adjusted timestamp.
+    ///
+    /// Do not override.
+    ///
+    /// See [`TimeSource::adjusted_timestamp()`].
+    fn adjusted_timestamp_send(&self) -> impl Future<Output = Result<Timestamp, Self::Err>> + Send
+    where
+        Self: Sized,
+    {
+        TimeSource::adjusted_timestamp(self)
+    }
+
+    /// Obtain an adjusted timestamp, extrapolated with the learned drift.
+    ///
+    /// Do not override.
+    ///
+    /// See [`TimeSource::extrapolated_timestamp()`].
+    fn extrapolated_timestamp_send(
+        &self,
+    ) -> impl Future<Output = Result<Timestamp, Self::Err>> + Send
+    where
+        Self: Sized,
+    {
+        TimeSource::extrapolated_timestamp(self)
+    }
+
+    /// Obtain an adjusted timestamp that never goes backwards.
+    ///
+    /// Do not override.
+    ///
+    /// See [`TimeSource::monotonic_timestamp()`].
+    fn monotonic_timestamp_send(
+        &self,
+        monotonic: &Monotonic,
+    ) -> impl Future<Output = Result<MonotonicTimestamp, Self::Err>> + Send
+    where
+        Self: Sized,
+    {
+        TimeSource::monotonic_timestamp(self, monotonic)
+    }
+
+    /// Obtain a clock that keeps synced time on the monotonic clock.
+    ///
+    /// Do not override.
+    ///
+    /// See [`TimeSource::synced_clock()`].
+    fn synced_clock_send(
+        &self,
+    ) -> impl Future<Output = Result<Option<SyncedClock>, Self::Err>> + Send
+    where
+        Self: Sized,
+    {
+        TimeSource::synced_clock(self)
+    }
+
+    /// Obtain the adjusted time as an interval.
+    ///
+    /// Do not override.
+    ///
+    /// See [`TimeSource::adjusted_bounds()`].
+    fn adjusted_bounds_send(
+        &self,
+        drift: u32,
+    ) -> impl Future<Output = Result<Option<TimeBounds>, Self::Err>> + Send
+    where
+        Self: Sized,
+    {
+        TimeSource::adjusted_bounds(self, drift)
+    }
+
+    /// Obtain the state of synchronisation.
+    ///
+    /// Do not override.
+    ///
+    /// See [`TimeSource::sync_state()`].
+    fn sync_state_send(
+        &self,
+        drift: u32,
+    ) -> impl Future<Output = Result<Option<SyncState>, Self::Err>> + Send
+    where
+        Self: Sized,
+    {
+        TimeSource::sync_state(self, drift)
+    }
+}
+
+impl<T: SendTimeSource> TimeSource for T {
+    type Err = T::Err;
+
+    async fn load_offset(&self) -> Result<Option<SignedDuration>, Self::Err> {
+        SendTimeSource::load_offset(self).await
+    }
+
+    fn clock(&self) -> &dyn Clock {
+        SendTimeSource::clock(self)
+    }
+
+    async fn load_sync_record(&self) -> Result<Option<SyncRecord>, Self::Err> {
+        SendTimeSource::load_sync_record(self).await
+    }
+}
+
+/// A time sync client, with `Send` futures.
+///
+/// This is the same as [`TimesimpClient`], but its futures are declared `Send`, so a sync loop can
+/// be spawned on a multi-threaded runtime from generic code:
+///
+/// ```no_run
+/// use timesimp::{SendTimesimpClient, Settings};
+///
+/// fn spawn_sync(mut client: impl SendTimesimpClient + 'static) {
+///     tokio::spawn(async move {
+///         loop {
+///             let _ = client.attempt_sync_send(Settings::default()).await;
+///             tokio::time::sleep(std::time::Duration::from_secs(60)).await;
+///         }
+///     });
+/// }
+/// ```
+///
+/// Implement this and [`SendTimeSource`] instead of `TimesimpClient` and `TimeSource`, which are
+/// then implemented for you. As with `SendTimeSource`, the provided methods are available here
+/// suffixed with `_send`, returning `Send` futures.
+pub trait SendTimesimpClient: SendTimeSource {
+    /// Store the current time offset.
+    ///
+    /// See [`TimesimpClient::store_offset()`].
+    fn store_offset(
+        &mut self,
+        offset: SignedDuration,
+    ) -> impl Future<Output = Result<(), Self::Err>> + Send;
+
+    /// Query a timesimp server endpoint.
+    ///
+    /// See [`TimesimpClient::query_server()`].
+    fn query_server(
+        &self,
+        request: Request,
+    ) -> impl Future<Output = Result<Response, Self::Err>> + Send;
+
+    /// Sleep for a [`Duration`].
+    ///
+    /// See [`TimesimpClient::sleep()`].
+    fn sleep(&self, duration: Duration) -> impl Future<Output = ()> + Send;
+
+    /// Store the record of the last successful sync.
+    ///
+    /// Optional: see [`TimesimpClient::store_sync_record()`].
+    fn store_sync_record(
+        &mut self,
+        record: SyncRecord,
+    ) -> impl Future<Output = Result<(), Self::Err>> + Send {
+        let _ = record;
+        async { Ok(()) }
+    }
+
+    /// Store the sample history.
+    ///
+    /// Optional: see [`TimesimpClient::store_samples()`].
+    fn store_samples(
+        &mut self,
+        samples: Vec<Sample>,
+    ) -> impl Future<Output = Result<(), Self::Err>> + Send {
+        let _ = samples;
+        async { Ok(()) }
+    }
+
+    /// Load the sample history.
+    ///
+    /// Optional: see [`TimesimpClient::load_samples()`].
+    fn load_samples(&self) -> impl Future<Output = Result<Vec<Sample>, Self::Err>> + Send {
+        async { Ok(Vec::new()) }
+    }
+
+    /// The main client state driver. Call this in a loop.
+    ///
+    /// Do not override.
+    ///
+    /// See [`TimesimpClient::attempt_sync()`].
+    fn attempt_sync_send(
+        &mut self,
+        settings: Settings,
+    ) -> impl Future<Output = Result<SignedDuration, SyncError<Self::Err>>> + Send
+    where
+        Self: Sized,
+    {
+        TimesimpClient::attempt_sync(self, settings)
+    }
+
+    /// The main client state driver, with a report of the attempt.
+    ///
+    /// Do not override.
+    ///
+    /// See [`TimesimpClient::attempt_sync_report()`].
+    fn attempt_sync_report_send(
+        &mut self,
+        settings: Settings,
+    ) -> impl Future<Output = Result<SyncReport, SyncError<Self::Err>>> + Send
+    where
+        Self: Sized,
+    {
+        TimesimpClient::attempt_sync_report(self, settings)
+    }
+
+    /// Wait until the true time is definitely after a timestamp.
+    ///
+    /// Do not override.
+    ///
+    /// See [`TimesimpClient::wait_until_after()`].
+    fn wait_until_after_send(
+        &self,
+        timestamp: Timestamp,
+        drift: u32,
+    ) -> impl Future<Output = Result<Option<TimeBounds>, Self::Err>> + Send
+    where
+        Self: Sized,
+    {
+        TimesimpClient::wait_until_after(self, timestamp, drift)
+    }
+}
+
+impl<T: SendTimesimpClient> TimesimpClient for T {
+    async fn store_offset(&mut self, offset: SignedDuration) -> Result<(), Self::Err> {
+        SendTimesimpClient::store_offset(self, offset).await
+    }
+
+    async fn query_server(&self, request: Request) -> Result<Response, Self::Err> {
+        SendTimesimpClient::query_server(self, request).await
+    }
+
+    async fn sleep(&self, duration: Duration) {
+        SendTimesimpClient::sleep(self, duration).await;
+    }
+
+    async fn store_sync_record(&mut self, record: SyncRecord) -> Result<(), Self::Err> {
+        SendTimesimpClient::store_sync_record(self, record).await
+    }
+
+    async fn store_samples(&mut self, samples: Vec<Sample>) -> Result<(), Self::Err> {
+        SendTimesimpClient::store_samples(self, samples).await
+    }
+
+    async fn load_samples(&self) -> Result<Vec<Sample>, Self::Err> {
+        SendTimesimpClient::load_samples(self).await
+    }
+}
+
+/// A time sync server, with `Send` futures.
+///
+/// This is the same as [`TimesimpServer`], but its endpoint implementation is available as
+/// [`answer_client_send()`](SendTimesimpServer::answer_client_send), returning a `Send` future.
+/// Implement this with an empty `impl` block, along with [`SendTimeSource`], instead of
+/// `TimesimpServer`, which is then implemented for you.
+pub trait SendTimesimpServer: SendTimeSource {
+    /// The implementation of the server endpoint.
+    ///
+    /// Do not override.
+    ///
+    /// See [`TimesimpServer::answer_client()`].
+    fn answer_client_send(
+        &self,
+        request: Request,
+    ) -> impl Future<Output = Result<Response, Self::Err>> + Send
+    where
+        Self: Sized,
+    {
+        TimesimpServer::answer_client(self, request)
+    }
+}
+
+impl<T: SendTimesimpServer> TimesimpServer for T {}
@@ -0,0 +1,22 @@
+use crate::{Request, Response, TimeSource};
+
+/// A time sync server.
+///
+/// There's nothing to implement beyond the [`TimeSource`]: implement this with an empty `impl`
+/// block, then use `answer_client()` to implement your server endpoint.
+#[allow(async_fn_in_trait)]
+pub trait TimesimpServer: TimeSource {
+    /// The implementation of the server endpoint.
+    ///
+    /// Do not override.
+    ///
+    /// Use this in your server endpoint implementation. Both [`Request`] and [`Response`] can be
+    /// parsed from and serialized to bytes. The endpoint should do as little else as possible to
+    /// avoid adding unnecessary latency.
+    async fn answer_client(&self, request: Request) -> Result<Response, Self::Err> {
+        Ok(Response {
+            client: request.client,
+            server: self.adjusted_timestamp().await?,
+        })
+    }
+}
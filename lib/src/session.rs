@@ -0,0 +1,667 @@
+use std::{
+    convert::Infallible,
+    time::{Duration, Instant},
+};
+
+use rand::rngs::StdRng;
+
+use crate::{
+    Clock, Delta, Request, Response, Sample, Settings, SyncError, SyncReport,
+    sampling::{self, Samples},
+};
+
+/// Identifies a query sent during a [`SyncSession`].
+///
+/// Give it back along with the query's response or failure.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct QueryId(u8);
+
+/// What a [`SyncSession`] needs done next.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Action {
+    /// Send this request to the server now.
+    ///
+    /// Then give the response to [`SyncSession::receive()`], or report the failure with
+    /// [`SyncSession::fail()`], along with the query's id.
+    Send(QueryId, Request),
+
+    /// Nothing to do until this instant, or until a response arrives, whichever is first.
+    ///
+    /// If `None`, there's nothing to do until a response arrives.
+    Wait(Option<Instant>),
+
+    /// The session is over.
+    ///
+    /// If it succeeded, apply the limits with [`Settings::check_offset()`] and store the offset
+    /// yourself. If it failed, the errors from failed queries are not known to the session, so
+    /// they're not included in the error.
+    Done(Result<SyncReport, SyncError<Infallible>>),
+}
+
+/// A sync attempt as a plain state machine.
+///
+/// This does the same sampling as
+/// [`TimesimpClient::attempt_sync()`](crate::TimesimpClient::attempt_sync), following the same
+/// [`Settings`], but without doing any I/O or sleeping itself, and without an async runtime. That
+/// makes it usable from a synchronous game loop, or a custom event loop.
+///
+/// Call [`poll()`](SyncSession::poll) and do what the [`Action`] says: send requests, give their
+/// responses back with the instant they arrived, and wait. Call `poll()` again after waiting,
+/// and after every response or failure, until it returns [`Action::Done`].
+///
+/// Storage is up to you: the session doesn't store the initial delta, the offset, the sync
+/// record, or the sample history. Use [`samples()`](SyncSession::samples) to keep a history.
+///
+/// # Example
+///
+/// ```no_run
+/// # use timesimp::{QueryId, Request, Response};
+/// # fn send(_: QueryId, _: Request) {}
+/// # fn receive() -> Option<(QueryId, Response)> { None }
+/// # fn wait(_: Option<std::time::Instant>) {}
+/// use std::time::Instant;
+/// use timesimp::{Action, Settings, SyncSession, SystemClock};
+///
+/// let mut session = SyncSession::new(Settings::default(), &SystemClock);
+/// let result = loop {
+///     match session.poll(&SystemClock) {
+///         Action::Send(id, request) => send(id, request),
+///         Action::Wait(until) => wait(until),
+///         Action::Done(result) => break result,
+///     }
+///
+///     while let Some((id, response)) = receive() {
+///         session.receive(id, response, Instant::now());
+///     }
+/// };
+/// match result {
+///     Ok(report) => println!("offset: {:?}", report.offset),
+///     Err(err) => eprintln!("{err}"),
+/// }
+/// ```
+#[derive(Debug, Clone)]
+pub struct SyncSession {
+    settings: Settings,
+    rng: StdRng,
+    started: Instant,
+    max_samples: u8,
+    sent: u8,
+    next_send: Duration,
+    pending: Vec<(QueryId, Instant)>,
+    round: Samples,
+    check_precision: bool,
+    result: Option<Result<SyncReport, SyncError<Infallible>>>,
+}
+
+impl SyncSession {
+    /// Start a session.
+    ///
+    /// The session's deadline and the initial delay count from now, as read from the clock.
+    pub fn new(settings: Settings, clock: &dyn Clock) -> Self {
+        let settings = settings.clamp();
+        let mut rng = sampling::gap_rng(settings.seed);
+        let next_send = sampling::initial_gap(&mut rng, settings.initial_delay);
+        let max_samples = settings
+            .adaptive
+            .map_or(settings.samples, |adaptive| adaptive.max_samples);
+        Self {
+            settings,
+            rng,
+            started: clock.instant(),
+            max_samples,
+            sent: 0,
+            next_send,
+            pending: Vec::with_capacity(settings.in_flight.into()),
+            round: Samples::with_capacity(max_samples),
+            check_precision: false,
+            result: None,
+        }
+    }
+
+    /// Find out what to do next.
+    ///
+    /// Requests are stamped with the time read from the clock, so send them right away.
+    ///
+    /// Queries that took longer than the timeout are abandoned here: a response arriving for one
+    /// of them later is ignored.
+    pub fn poll(&mut self, clock: &dyn Clock) -> Action {
+        if let Some(result) = &self.result {
+            return Action::Done(result.clone());
+        }
+
+        let Settings {
+            samples,
+            jitter,
+            spacing,
+            outliers,
+            adaptive,
+            timeout,
+            deadline,
+            in_flight,
+            ..
+        } = self.settings;
+
+        let now = clock.instant();
+        let elapsed = now.duration_since(self.started);
+
+        if let Some(timeout) = timeout {
+            let before = self.pending.len();
+            self.pending
+                .retain(|(_, sent)| now.duration_since(*sent) < timeout);
+            let expired = before - self.pending.len();
+            if expired > 0 {
+                tracing::error!(?timeout, "query timed out");
+                self.round.failures += expired;
+                self.round.timeouts += expired;
+                self.completed(now);
+            }
+        }
+
+        if let Some(deadline) = deadline
+            && elapsed >= deadline
+        {
+            tracing::error!(
+                ?deadline,
+                in_flight = self.pending.len(),
+                "deadline reached while querying, stopping"
+            );
+            self.round.failures += self.pending.len();
+            self.round.deadline_reached = true;
+            return self.finish();
+        }
+
+        if self.check_precision {
+            self.check_precision = false;
+            if self.round.precise_enough(samples, adaptive, outliers) {
+                return self.finish();
+            }
+        }
+
+        let can_send = self.pending.len() < in_flight.into()
+            && self.sent < self.max_samples
+            && deadline.is_none_or(|deadline| self.next_send < deadline);
+        if can_send && self.next_send <= elapsed {
+            let id = QueryId(self.sent);
+            let request = self.round.request(clock);
+            tracing::trace!(in_flight = self.pending.len(), "sending query");
+            self.pending.push((id, now));
+            self.sent += 1;
+            if in_flight > 1 {
+                self.next_send = self.next_send.max(elapsed)
+                    + sampling::random_gap(&mut self.rng, jitter, spacing);
+            }
+            return Action::Send(id, request);
+        }
+
+        if !can_send && self.pending.is_empty() {
+            if self.sent < self.max_samples {
+                tracing::debug!(
+                    next_send = ?self.next_send,
+                    "next sample would be past the deadline, stopping"
+                );
+                self.round.deadline_reached = true;
+            }
+            return self.finish();
+        }
+
+        let wake = [
+            can_send.then(|| self.started + self.next_send),
+            deadline.map(|deadline| self.started + deadline),
+            timeout.and_then(|timeout| self.pending.iter().map(|(_, sent)| *sent + timeout).min()),
+        ]
+        .into_iter()
+        .flatten()
+        .min();
+        Action::Wait(wake)
+    }
+
+    /// Give the response to a query, and the instant it arrived.
+    ///
+    /// Responses can be given in any order. Responses to queries that are no longer in flight,
+    /// such as those that timed out, are ignored. A response that arrived after the timeout counts
+    /// as a timeout, even if `poll()` wasn't called in between to abandon its query.
+    pub fn receive(&mut self, id: QueryId, response: Response, at: Instant) {
+        let Some(sent) = self.take_pending(id) else {
+            tracing::debug!(?id, ?response, "query is not in flight, ignoring response");
+            return;
+        };
+
+        let round_trip = at.saturating_duration_since(sent);
+        if let Some(timeout) = self.settings.timeout
+            && round_trip >= timeout
+        {
+            tracing::error!(?timeout, ?round_trip, "query timed out");
+            self.round.failures += 1;
+            self.round.timeouts += 1;
+            self.completed(at);
+            return;
+        }
+
+        let packet = Delta::new(response, round_trip, self.settings.asymmetry);
+        tracing::trace!(
+            latency = ?packet.latency,
+            delta = ?packet.delta,
+            "obtained raw offset from server"
+        );
+        self.round.deltas.push(packet);
+        self.check_precision = true;
+        self.completed(at);
+    }
+
+    /// Report that a query failed, and the instant it did.
+    ///
+    /// Failures of queries that are no longer in flight, such as those that timed out, are
+    /// ignored.
+    pub fn fail(&mut self, id: QueryId, at: Instant) {
+        if self.take_pending(id).is_none() {
+            return;
+        }
+
+        tracing::debug!(?id, "query failed");
+        self.round.failures += 1;
+        self.completed(at);
+    }
+
+    /// The samples obtained so far.
+    ///
+    /// Give these to [`TimesimpClient::store_samples()`](crate::TimesimpClient::store_samples)
+    /// or your own storage to keep a sample history across sessions.
+    pub fn samples(&self) -> Vec<Sample> {
+        self.round
+            .deltas
+            .iter()
+            .copied()
+            .map(Sample::from)
+            .collect()
+    }
+
+    fn take_pending(&mut self, id: QueryId) -> Option<Instant> {
+        let index = self.pending.iter().position(|(query, _)| *query == id)?;
+        Some(self.pending.swap_remove(index).1)
+    }
+
+    /// When sampling sequentially, the gap to the next sample starts when a query completes.
+    fn completed(&mut self, at: Instant) {
+        if self.settings.in_flight == 1 {
+            self.next_send = at.saturating_duration_since(self.started)
+                + sampling::random_gap(&mut self.rng, self.settings.jitter, self.settings.spacing);
+        }
+    }
+
+    /// Whether a query is still in flight.
+    pub(crate) fn is_pending(&self, id: QueryId) -> bool {
+        self.pending.iter().any(|(query, _)| *query == id)
+    }
+
+    /// The samples gathered so far.
+    pub(crate) fn round(&self) -> &Samples {
+        &self.round
+    }
+
+    /// The samples gathered, once the session is over.
+    pub(crate) fn into_round(self) -> Samples {
+        self.round
+    }
+
+    fn finish(&mut self) -> Action {
+        self.pending.clear();
+        let mut round = self.round.clone();
+        let result = round.check().and_then(|()| {
+            let deltas = round.deltas.clone();
+            round
+                .conclude(deltas, self.settings.outliers)
+                .map(|(_, report)| report)
+        });
+        self.result = Some(result.clone());
+        Action::Done(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use jiff::{SignedDuration, Timestamp};
+
+    use super::*;
+    use crate::{Adaptive, Spacing};
+
+    /// Simulated time, advanced by hand.
+    #[derive(Debug)]
+    struct SimClock {
+        instant: Instant,
+        wall: Timestamp,
+        elapsed: Mutex<Duration>,
+        step: Mutex<SignedDuration>,
+    }
+
+    impl SimClock {
+        fn new() -> Self {
+            Self {
+                instant: Instant::now(),
+                wall: Timestamp::from_second(1_700_000_000).unwrap(),
+                elapsed: Mutex::new(Duration::ZERO),
+                step: Mutex::new(SignedDuration::ZERO),
+            }
+        }
+
+        /// Step the wall clock, without affecting the monotonic clock.
+        fn step(&self, by: SignedDuration) {
+            *self.step.lock().unwrap() += by;
+        }
+
+        fn advance_to(&self, instant: Instant) {
+            let mut elapsed = self.elapsed.lock().unwrap();
+            *elapsed = (*elapsed).max(instant.duration_since(self.instant));
+        }
+    }
+
+    impl Clock for SimClock {
+        fn now(&self) -> Timestamp {
+            self.wall + *self.elapsed.lock().unwrap() + *self.step.lock().unwrap()
+        }
+
+        fn instant(&self) -> Instant {
+            self.instant + *self.elapsed.lock().unwrap()
+        }
+    }
+
+    fn settings() -> Settings {
+        Settings {
+            seed: Some(42),
+            ..Default::default()
+        }
+    }
+
+    /// Run a session against a server ahead by `offset`, with a fixed round trip.
+    ///
+    /// Requests for which `answer` returns false get no response.
+    fn run(
+        settings: Settings,
+        offset: SignedDuration,
+        round_trip: Duration,
+        mut answer: impl FnMut(usize) -> bool,
+    ) -> (Result<SyncReport, SyncError<Infallible>>, Duration) {
+        let clock = SimClock::new();
+        let mut session = SyncSession::new(settings, &clock);
+        let mut in_flight: Vec<(Instant, QueryId, Response)> = Vec::new();
+        let mut sent = 0;
+        loop {
+            match session.poll(&clock) {
+                Action::Send(id, request) => {
+                    if answer(sent) {
+                        in_flight.push((
+                            clock.instant() + round_trip,
+                            id,
+                            Response {
+                                client: request.client,
+                                server: clock.now() + offset + round_trip / 2,
+                            },
+                        ));
+                    }
+                    sent += 1;
+                }
+                Action::Wait(until) => {
+                    in_flight.sort_by_key(|(arrival, _, _)| *arrival);
+                    let arrival = in_flight.first().map(|(arrival, _, _)| *arrival);
+                    match (arrival, until) {
+                        (Some(arrival), until) if until.is_none_or(|until| arrival <= until) => {
+                            let (arrival, id, response) = in_flight.remove(0);
+                            clock.advance_to(arrival);
+                            session.receive(id, response, arrival);
+                        }
+                        (_, Some(until)) => clock.advance_to(until),
+                        _ => panic!("waiting forever"),
+                    }
+                }
+                Action::Done(result) => {
+                    return (result, clock.instant().duration_since(clock.instant));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn sequential() {
+        let offset = SignedDuration::from_secs(5);
+        let (report, _) = run(settings(), offset, Duration::from_millis(20), |_| true);
+        let report = report.unwrap();
+        assert_eq!(report.offset, offset);
+        assert_eq!(report.error, Duration::from_millis(10));
+        assert_eq!(report.samples, 5);
+        assert_eq!(report.failures, 0);
+        assert!(!report.deadline_reached);
+    }
+
+    #[test]
+    fn pipelined_is_faster() {
+        let offset = SignedDuration::from_millis(-300);
+        let round_trip = Duration::from_millis(500);
+        let (sequential, sequential_time) = run(settings(), offset, round_trip, |_| true);
+        let (pipelined, pipelined_time) = run(
+            Settings {
+                in_flight: 5,
+                ..settings()
+            },
+            offset,
+            round_trip,
+            |_| true,
+        );
+        assert_eq!(sequential.unwrap().offset, offset);
+        let pipelined = pipelined.unwrap();
+        assert_eq!(pipelined.offset, offset);
+        assert_eq!(pipelined.samples, 5);
+        assert!(pipelined_time < sequential_time);
+    }
+
+    #[test]
+    fn timeouts() {
+        let (report, _) = run(
+            Settings {
+                timeout: Some(Duration::from_millis(100)),
+                ..settings()
+            },
+            SignedDuration::ZERO,
+            Duration::from_millis(20),
+            |n| n % 2 == 0,
+        );
+        let report = report.unwrap();
+        assert_eq!(report.samples, 3);
+        assert_eq!(report.failures, 2);
+        assert_eq!(report.timeouts, 2);
+        assert_eq!(report.offset, SignedDuration::ZERO);
+    }
+
+    #[test]
+    fn all_timed_out() {
+        let (result, _) = run(
+            Settings {
+                timeout: Some(Duration::from_millis(100)),
+                ..settings()
+            },
+            SignedDuration::ZERO,
+            Duration::from_millis(20),
+            |_| false,
+        );
+        assert_eq!(
+            result,
+            Err(SyncError::AllQueriesFailed {
+                errors: Vec::new(),
+                timeouts: 5,
+            })
+        );
+    }
+
+    #[test]
+    fn deadline() {
+        let (result, elapsed) = run(
+            Settings {
+                deadline: Some(Duration::from_millis(500)),
+                ..settings()
+            },
+            SignedDuration::ZERO,
+            Duration::from_millis(20),
+            |_| false,
+        );
+        assert_eq!(
+            result,
+            Err(SyncError::TooFewInliers {
+                samples: 0,
+                errors: Vec::new(),
+                timeouts: 0,
+                deadline_reached: true,
+            })
+        );
+        assert_eq!(elapsed, Duration::from_millis(500));
+    }
+
+    #[test]
+    fn adaptive_stops_early() {
+        let (report, _) = run(
+            Settings {
+                adaptive: Some(Adaptive {
+                    target: Duration::from_millis(1),
+                    max_samples: 31,
+                }),
+                ..settings()
+            },
+            SignedDuration::ZERO,
+            Duration::from_millis(20),
+            |_| true,
+        );
+        assert_eq!(report.unwrap().samples, 5);
+    }
+
+    #[test]
+    fn failures_and_late_responses() {
+        let clock = SimClock::new();
+        let mut session = SyncSession::new(settings(), &clock);
+        let Action::Send(id, request) = session.poll(&clock) else {
+            panic!("expected a request");
+        };
+        session.fail(id, clock.instant());
+
+        // a response for a request that already failed is ignored
+        session.receive(
+            id,
+            Response {
+                client: request.client,
+                server: request.client,
+            },
+            clock.instant(),
+        );
+        assert_eq!(session.round.failures, 1);
+        assert!(session.samples().is_empty());
+
+        let Action::Wait(Some(until)) = session.poll(&clock) else {
+            panic!("expected to wait for the next sample");
+        };
+        clock.advance_to(until);
+        assert!(matches!(session.poll(&clock), Action::Send(..)));
+    }
+
+    #[test]
+    fn late_response_before_poll() {
+        let clock = SimClock::new();
+        let mut session = SyncSession::new(
+            Settings {
+                timeout: Some(Duration::from_millis(100)),
+                ..settings()
+            },
+            &clock,
+        );
+        let Action::Send(id, request) = session.poll(&clock) else {
+            panic!("expected a request");
+        };
+
+        // the response arrives past the timeout, before the session was polled to expire it
+        session.receive(
+            id,
+            Response {
+                client: request.client,
+                server: request.client,
+            },
+            clock.instant() + Duration::from_millis(150),
+        );
+        assert_eq!(session.round.failures, 1);
+        assert_eq!(session.round.timeouts, 1);
+        assert!(session.samples().is_empty());
+    }
+
+    #[test]
+    fn requests_with_the_same_timestamp() {
+        let clock = SimClock::new();
+        let mut session = SyncSession::new(
+            Settings {
+                jitter: Duration::from_secs(1),
+                spacing: Spacing::Fixed {
+                    spread: Duration::ZERO,
+                },
+                in_flight: 2,
+                ..settings()
+            },
+            &clock,
+        );
+        let Action::Send(first, first_request) = session.poll(&clock) else {
+            panic!("expected a request");
+        };
+        let Action::Wait(Some(until)) = session.poll(&clock) else {
+            panic!("expected to wait for the next sample");
+        };
+
+        // the wall clock is stepped back by exactly the gap, so both requests have the same time
+        clock.advance_to(until);
+        clock.step(SignedDuration::from_secs(-1));
+        let Action::Send(second, second_request) = session.poll(&clock) else {
+            panic!("expected a request");
+        };
+        assert_eq!(first_request, second_request);
+
+        let response = Response {
+            client: first_request.client,
+            server: first_request.client,
+        };
+        session.receive(second, response, until + Duration::from_millis(10));
+        session.receive(first, response, until + Duration::from_millis(50));
+
+        let mut latencies = session
+            .samples()
+            .iter()
+            .map(|sample| sample.latency)
+            .collect::<Vec<_>>();
+        latencies.sort();
+        assert_eq!(
+            latencies,
+            vec![Duration::from_millis(5), Duration::from_millis(525)]
+        );
+    }
+
+    #[test]
+    fn clock_went_backwards() {
+        let clock = SimClock::new();
+        let mut session = SyncSession::new(settings(), &clock);
+        let mut sent = 0;
+        let result = loop {
+            match session.poll(&clock) {
+                Action::Send(id, request) => {
+                    sent += 1;
+                    if sent == 2 {
+                        clock.step(SignedDuration::from_secs(-10));
+                    }
+                    let response = Response {
+                        client: request.client,
+                        server: request.client,
+                    };
+                    session.receive(id, response, clock.instant());
+                }
+                Action::Wait(Some(until)) => clock.advance_to(until),
+                Action::Wait(None) => panic!("waiting forever"),
+                Action::Done(result) => break result,
+            }
+        };
+        assert!(
+            matches!(result, Err(SyncError::ClockWentBackwards { by }) if by > Duration::from_secs(7)),
+            "{result:?}"
+        );
+    }
+}
@@ -1,6 +1,10 @@
 use std::time::Duration;
 
-/// Settings for a [`Timesimp`](crate::Timesimp).
+use jiff::SignedDuration;
+
+use crate::SyncError;
+
+/// Settings for a [`TimesimpClient`](crate::TimesimpClient).
 ///
 /// Values set will be clamped to acceptable ones before use (e.g. setting samples to 10 will
 /// result in a value of 11 being selected).
@@ -9,14 +13,263 @@ pub struct Settings {
     /// How many samples to gather for synchronisation.
     ///
     /// Must be odd, minimum 3, default 5.
+    ///
+    /// With [`adaptive`](Settings::adaptive) sampling, this is the minimum number of samples.
     pub samples: u8,
 
-    /// The maximum amount of time between taking two samples.
+    /// The scale of the time between taking two samples.
     ///
-    /// The actual value will be random.
+    /// The actual value will be random, as picked by the [`spacing`](Settings::spacing). With the
+    /// default uniform spacing this is the maximum gap, but with [`Spacing::Fixed`] the gap may be
+    /// up to twice this, and with [`Spacing::Exponential`] up to five times this.
     ///
-    /// Must be more than 10µs, less than 10s, default 100ms.
+    /// Must be more than 10µs, less than 10s, default 2s.
     pub jitter: Duration,
+
+    /// How the time between two samples is picked.
+    ///
+    /// Default is [`Spacing::Uniform`].
+    pub spacing: Spacing,
+
+    /// The maximum delay before taking the first sample.
+    ///
+    /// If set, the first sample is delayed by a random amount up to this, uniformly distributed.
+    /// Set this so a fleet of clients starting at the same instant doesn't stampede the server.
+    ///
+    /// Must be less than 1 minute, default is no delay.
+    pub initial_delay: Option<Duration>,
+
+    /// Known asymmetry between the outbound and return legs of a round trip.
+    ///
+    /// Default is to assume the trip is symmetric.
+    pub asymmetry: Asymmetry,
+
+    /// How outliers are eliminated from the samples.
+    ///
+    /// Default is [`OutlierFilter::StdDev`].
+    pub outliers: OutlierFilter,
+
+    /// Adaptive sampling.
+    ///
+    /// If set, the sync stops as soon as the estimate is precise enough, and otherwise keeps
+    /// sampling up to a maximum. Default is to always take exactly `samples` samples.
+    pub adaptive: Option<Adaptive>,
+
+    /// The maximum amount of time to wait for a single sample.
+    ///
+    /// If `query_server()` takes longer than this, the sample is abandoned and counted as a
+    /// failure. The timeout is enforced using
+    /// [`TimesimpClient::sleep()`](crate::TimesimpClient::sleep).
+    ///
+    /// Must be more than 1ms, default is no timeout.
+    pub timeout: Option<Duration>,
+
+    /// The maximum amount of time a whole sync attempt may take.
+    ///
+    /// When it runs out, the attempt stops sampling and finishes with the samples it has, if
+    /// they're enough for confidence. A query still in flight at that point is abandoned.
+    ///
+    /// Must be more than 1ms, default is no deadline.
+    pub deadline: Option<Duration>,
+
+    /// How many queries may be in flight at once.
+    ///
+    /// By default, queries are sent one after the other: the next one is only sent a random gap
+    /// after the previous one got its response. Over high-latency links, that makes a sync take
+    /// many round trips. With more than one query allowed in flight, the next query is sent a
+    /// random gap after the previous one was *sent*, so several may be waiting for responses at
+    /// once. Each response is matched to the time of its own request.
+    ///
+    /// Your `query_server()` implementation must support being called concurrently for this to
+    /// be useful, for example by using a multiplexed transport or several connections. When
+    /// pipelining, if no offset is stored yet, the first delta is only stored once no queries are
+    /// in flight, which may be at the end of the round.
+    ///
+    /// Minimum 1, default 1.
+    pub in_flight: u8,
+
+    /// Keep a history of samples across sync attempts.
+    ///
+    /// If set, the samples of each attempt are added to those kept from previous attempts with
+    /// [`TimesimpClient::store_samples()`](crate::TimesimpClient::store_samples), and the offset is
+    /// estimated from the whole window. That smooths the offset over several attempts, and lets a
+    /// restarted client pick up where it left off. If an attempt gets no samples, no offset is
+    /// estimated, regardless of the history.
+    ///
+    /// Default is no history: the offset is estimated from each attempt's samples only.
+    pub history: Option<History>,
+
+    /// Seed for the random gaps between samples.
+    ///
+    /// By default, the gaps are drawn from a generator seeded from the operating system. Set this
+    /// to make the timing of a sync reproducible, for example in tests and simulations.
+    pub seed: Option<u64>,
+
+    /// The largest offset that may be stored, in either direction.
+    ///
+    /// A broken server (say, one returning a timestamp from 1970) would otherwise shift this
+    /// client by whatever it says. If the calculated offset is larger than this, it's not stored,
+    /// and [`SyncError::OffsetTooLarge`](crate::SyncError::OffsetTooLarge) is returned.
+    ///
+    /// Default is no limit.
+    pub max_offset: Option<Duration>,
+
+    /// The largest change from the current offset that may be stored, in either direction.
+    ///
+    /// If the calculated offset is further than this from the stored one, it's not stored, and
+    /// [`SyncError::ChangeTooLarge`](crate::SyncError::ChangeTooLarge) is returned. This does not
+    /// apply when no offset is stored yet.
+    ///
+    /// Default is no limit.
+    pub max_change: Option<Duration>,
+
+    /// Whether the first sync may set any offset.
+    ///
+    /// If true and no offset is stored yet, `max_offset` is not checked. This is similar to the
+    /// `-g` option of ntpd: a client may start with a wildly wrong clock, but once synced, it
+    /// should not be stepped arbitrarily.
+    ///
+    /// Default false.
+    pub first_sync_may_step: bool,
+}
+
+/// Settings for adaptive sampling.
+///
+/// Once the minimum number of [`samples`](Settings::samples) is obtained, the offset is estimated
+/// after every further sample, and sampling stops as soon as the estimate's standard error (the
+/// standard deviation of the samples kept, over the square root of their count) is within the
+/// target. That way, a stable link is done quickly, and a noisy link gets more samples.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Adaptive {
+    /// The standard error to reach before stopping.
+    pub target: Duration,
+
+    /// The maximum amount of samples to gather.
+    ///
+    /// Must be at least [`samples`](Settings::samples), maximum 255.
+    pub max_samples: u8,
+}
+
+/// How the time between two samples is picked.
+///
+/// Periodic traffic on some networks can alias with a regular sampling pattern; pick the
+/// distribution that suits the network.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum Spacing {
+    /// Uniformly random between zero and the [`jitter`](Settings::jitter).
+    #[default]
+    Uniform,
+
+    /// Exponentially random, so that samples form a Poisson process.
+    ///
+    /// The mean is half the jitter, the same as uniform spacing, and gaps are capped at five times
+    /// the jitter.
+    Exponential,
+
+    /// The jitter, give or take up to a spread, uniformly random.
+    ///
+    /// The spread is capped at the jitter.
+    Fixed {
+        /// How far the gap may be from the jitter, either way.
+        spread: Duration,
+    },
+
+    /// Uniformly random between this minimum and the jitter.
+    ///
+    /// The minimum is capped at the jitter.
+    MinGap(Duration),
+}
+
+/// Settings for the sample history.
+///
+/// Samples are kept while they're within the window, up to a maximum; the most recent are kept.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct History {
+    /// How old samples may be.
+    pub window: Duration,
+
+    /// The maximum amount of samples to keep.
+    ///
+    /// Minimum 3.
+    pub max_samples: u16,
+}
+
+/// How outliers are eliminated from the samples before averaging them.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum OutlierFilter {
+    /// Keep samples within one standard deviation of the median.
+    ///
+    /// This is the method described by Simpson, with the median taken as the middle sample by
+    /// latency. As the standard deviation is computed over all samples, a single huge spike
+    /// widens the window enough to let other bad samples in.
+    #[default]
+    StdDev,
+
+    /// Keep samples within three median absolute deviations of the median.
+    ///
+    /// The MAD is scaled to estimate the standard deviation. This is robust to spikes, so long as
+    /// fewer than half the samples are bad. If more than half the samples are exactly equal, only
+    /// those are kept.
+    Mad,
+
+    /// Keep samples within Tukey's fences: one and a half interquartile ranges outside the first
+    /// and third quartiles.
+    ///
+    /// This is robust to spikes, so long as fewer than a quarter of the samples are bad on either
+    /// side.
+    Iqr,
+}
+
+/// Known asymmetry between the outbound and return legs of a round trip.
+///
+/// The offset is calculated by assuming the server stamped its response at some point during the
+/// round trip. By default, that's the midpoint; if the path is known to be asymmetric, that
+/// error can be compensated for by moving the point.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum Asymmetry {
+    /// Both legs take the same time.
+    #[default]
+    Symmetric,
+
+    /// The legs take time in this proportion.
+    ///
+    /// For example, `Ratio { outbound: 3, inbound: 2 }` means the outbound leg (client to server)
+    /// takes three fifths of the round trip, and the return leg two fifths.
+    ///
+    /// If both are zero, this is the same as `Symmetric`.
+    Ratio {
+        /// The share of the round trip taken by the outbound leg.
+        outbound: u16,
+
+        /// The share of the round trip taken by the return leg.
+        inbound: u16,
+    },
+
+    /// The outbound leg takes this much longer than the return leg.
+    ///
+    /// If negative, the outbound leg is shorter than the return leg. If it's larger than a
+    /// particular round trip, that round trip is considered to be entirely outbound (or return).
+    Bias(SignedDuration),
+}
+
+impl Asymmetry {
+    /// Split a round trip time into the outbound leg.
+    pub(crate) fn outbound(self, round_trip: SignedDuration) -> SignedDuration {
+        match self {
+            Self::Symmetric
+            | Self::Ratio {
+                outbound: 0,
+                inbound: 0,
+            } => round_trip / 2,
+            Self::Ratio { outbound, inbound } => SignedDuration::from_nanos(
+                (round_trip.as_nanos() * i128::from(outbound)
+                    / (i128::from(outbound) + i128::from(inbound))) as i64,
+            ),
+            Self::Bias(bias) => {
+                (round_trip.saturating_add(bias) / 2).clamp(SignedDuration::ZERO, round_trip)
+            }
+        }
+    }
 }
 
 impl Default for Settings {
@@ -24,6 +277,19 @@ impl Default for Settings {
         Self {
             samples: 5,
             jitter: Duration::from_secs(2),
+            spacing: Spacing::Uniform,
+            initial_delay: None,
+            asymmetry: Asymmetry::Symmetric,
+            outliers: OutlierFilter::StdDev,
+            adaptive: None,
+            timeout: None,
+            deadline: None,
+            in_flight: 1,
+            history: None,
+            seed: None,
+            max_offset: None,
+            max_change: None,
+            first_sync_may_step: false,
         }
     }
 }
@@ -31,16 +297,73 @@ impl Default for Settings {
 impl Settings {
     /// Clamp to acceptable values.
     pub(crate) fn clamp(self) -> Self {
+        let samples = if self.samples.is_multiple_of(2) {
+            self.samples.saturating_add(1)
+        } else {
+            self.samples
+        }
+        .clamp(3, 255);
+
         Self {
-            samples: if self.samples % 2 == 0 {
-                self.samples.saturating_add(1)
-            } else {
-                self.samples
-            }
-            .clamp(3, 255),
+            samples,
             jitter: self
                 .jitter
                 .clamp(Duration::from_micros(10), Duration::from_secs(10)),
+            spacing: self.spacing,
+            initial_delay: self
+                .initial_delay
+                .map(|delay| delay.min(Duration::from_secs(60))),
+            asymmetry: self.asymmetry,
+            outliers: self.outliers,
+            adaptive: self.adaptive.map(|adaptive| Adaptive {
+                target: adaptive.target,
+                max_samples: adaptive.max_samples.max(samples),
+            }),
+            timeout: self
+                .timeout
+                .map(|timeout| timeout.max(Duration::from_millis(1))),
+            deadline: self
+                .deadline
+                .map(|deadline| deadline.max(Duration::from_millis(1))),
+            in_flight: self.in_flight.max(1),
+            history: self.history.map(|history| History {
+                window: history.window,
+                max_samples: history.max_samples.max(3),
+            }),
+            seed: self.seed,
+            max_offset: self.max_offset,
+            max_change: self.max_change,
+            first_sync_may_step: self.first_sync_may_step,
         }
     }
+
+    /// Check a calculated offset against the limits, given the offset currently stored.
+    ///
+    /// The sync methods do this for you; use it to apply the limits to the result of a
+    /// [`SyncSession`](crate::SyncSession).
+    pub fn check_offset<E>(
+        &self,
+        current: Option<SignedDuration>,
+        offset: SignedDuration,
+    ) -> Result<(), SyncError<E>> {
+        if let Some(max) = self.max_offset
+            && !(current.is_none() && self.first_sync_may_step)
+            && offset.unsigned_abs() > max
+        {
+            return Err(SyncError::OffsetTooLarge { offset, max });
+        }
+
+        if let Some(max) = self.max_change
+            && let Some(current) = current
+            && (offset - current).unsigned_abs() > max
+        {
+            return Err(SyncError::ChangeTooLarge {
+                offset,
+                current,
+                max,
+            });
+        }
+
+        Ok(())
+    }
 }
@@ -0,0 +1,154 @@
+use jiff::{SignedDuration, Timestamp};
+
+use crate::{
+    Clock, Monotonic, MonotonicTimestamp, SyncRecord, SyncState, SyncedClock, SystemClock,
+    TimeBounds,
+};
+
+/// A source of adjusted time.
+///
+/// This is the part common to time sync clients and servers: loading the offset, and reading the
+/// adjusted time with it. You must implement the required function and not override the others,
+/// except for the optional hooks, which enable additional features.
+///
+/// Then, implement [`TimesimpServer`](crate::TimesimpServer) to answer clients, and/or
+/// [`TimesimpClient`](crate::TimesimpClient) to sync with a server.
+#[allow(async_fn_in_trait)]
+pub trait TimeSource {
+    /// Error for your required methods.
+    type Err: std::error::Error;
+
+    /// Load the current time offset.
+    ///
+    /// This must return the current stored time offset, or `None` if no time offset is currently
+    /// stored.
+    ///
+    /// As this expects a `SignedDuration`, you are free to load whatever precision you wish, so
+    /// long as `store_offset()` agrees. Microseconds should be enough for most purposes.
+    async fn load_offset(&self) -> Result<Option<SignedDuration>, Self::Err>;
+
+    /// The clock to read the time from.
+    ///
+    /// Optional: by default this is the [`SystemClock`]. All of the client and server logic reads
+    /// the time through this, so override it to run on another timescale, or on simulated time.
+    fn clock(&self) -> &dyn Clock {
+        &SystemClock
+    }
+
+    /// Load the record of the last successful sync.
+    ///
+    /// Optional: by default this returns `None`. This must return the last record given to
+    /// `store_sync_record()`, or `None` if there's none.
+    async fn load_sync_record(&self) -> Result<Option<SyncRecord>, Self::Err> {
+        Ok(None)
+    }
+
+    /// Obtain an adjusted timestamp.
+    ///
+    /// Do not override.
+    ///
+    /// This simply loads the offset and applies it to the current local timestamp, as read from
+    /// the [`clock()`](TimeSource::clock).
+    ///
+    /// It is provided as convenience for simple use; you may want to implement your own.
+    async fn adjusted_timestamp(&self) -> Result<Timestamp, Self::Err> {
+        let offset = self.load_offset().await?.unwrap_or_default();
+        Ok(self.clock().now() + offset)
+    }
+
+    /// Obtain an adjusted timestamp, extrapolated with the learned drift.
+    ///
+    /// Do not override.
+    ///
+    /// This applies the offset of the last sync, extrapolated to now with the drift learned over
+    /// successive syncs (see [`SyncRecord::offset_at()`]), to the current local timestamp. It's
+    /// the centre of [`adjusted_bounds()`](TimeSource::adjusted_bounds), and keeps better time
+    /// than [`adjusted_timestamp()`](TimeSource::adjusted_timestamp) when syncs are far apart.
+    ///
+    /// Falls back to the stored offset as is if there's no sync record, including if the storage
+    /// hooks are not implemented.
+    async fn extrapolated_timestamp(&self) -> Result<Timestamp, Self::Err> {
+        let Some(record) = self.load_sync_record().await? else {
+            return self.adjusted_timestamp().await;
+        };
+
+        let now = self.clock().now();
+        Ok(now + record.offset_at(now))
+    }
+
+    /// Obtain an adjusted timestamp that never goes backwards.
+    ///
+    /// Do not override.
+    ///
+    /// This passes [`adjusted_timestamp()`](TimeSource::adjusted_timestamp) through the given
+    /// [`Monotonic`] guard, so that successive calls sharing the guard (from any thread) return
+    /// non-decreasing timestamps even if the offset decreases or the system clock is stepped back.
+    /// Use this if you need adjusted timestamps for ordering events.
+    async fn monotonic_timestamp(
+        &self,
+        monotonic: &Monotonic,
+    ) -> Result<MonotonicTimestamp, Self::Err> {
+        Ok(monotonic.observe(self.adjusted_timestamp().await?))
+    }
+
+    /// Obtain a clock that keeps synced time on the monotonic clock.
+    ///
+    /// Do not override.
+    ///
+    /// This loads the offset and anchors it to the current instant; see [`SyncedClock`]. Call it
+    /// right after a successful sync. Returns `None` if no offset is stored.
+    async fn synced_clock(&self) -> Result<Option<SyncedClock>, Self::Err> {
+        Ok(self
+            .load_offset()
+            .await?
+            .map(|offset| SyncedClock::with_clock(offset, self.clock())))
+    }
+
+    /// Obtain the adjusted time as an interval.
+    ///
+    /// Do not override.
+    ///
+    /// The interval is centered on
+    /// [`extrapolated_timestamp()`](TimeSource::extrapolated_timestamp), and is as wide as the
+    /// error of the last sync, widened by the time elapsed since at the assumed `drift` rate in
+    /// parts per billion (see [`SyncRecord::error_at()`]). Use this if you need to know how wrong
+    /// the adjusted time might be.
+    ///
+    /// Returns `None` if there's no sync record, including if the storage hooks are not
+    /// implemented.
+    async fn adjusted_bounds(&self, drift: u32) -> Result<Option<TimeBounds>, Self::Err> {
+        let Some(record) = self.load_sync_record().await? else {
+            return Ok(None);
+        };
+
+        let now = self.clock().now();
+        Ok(Some(TimeBounds::new(
+            now + record.offset_at(now),
+            record.error_at(now, drift),
+        )))
+    }
+
+    /// Obtain the state of synchronisation.
+    ///
+    /// Do not override.
+    ///
+    /// This reports how long it's been since the last good sync, whether the client is in
+    /// holdover because sync attempts failed since, the offset extrapolated with the learned
+    /// drift, and its uncertainty, which grows at the assumed `drift` rate in parts per billion
+    /// (see [`SyncRecord::error_at()`]). The adjusted time at that offset is
+    /// [`extrapolated_timestamp()`](TimeSource::extrapolated_timestamp).
+    ///
+    /// Returns `None` if there's no sync record, including if the storage hooks are not
+    /// implemented.
+    async fn sync_state(&self, drift: u32) -> Result<Option<SyncState>, Self::Err> {
+        let Some(record) = self.load_sync_record().await? else {
+            return Ok(None);
+        };
+
+        Ok(Some(SyncState::from_record(
+            record,
+            self.clock().now(),
+            drift,
+        )))
+    }
+}
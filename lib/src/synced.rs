@@ -0,0 +1,157 @@
+use std::time::Instant;
+
+use jiff::{SignedDuration, SpanRelativeTo, Timestamp};
+
+use crate::{Clock, SystemClock};
+
+/// A clock that keeps synced time on the monotonic clock.
+///
+/// An offset is only meaningful relative to the system clock at the moment it was computed: if
+/// the system clock is stepped afterwards (by NTP, or by hand), `adjusted_timestamp()` is silently
+/// wrong until the next sync. Instead, this anchors the synced time to an [`Instant`], and derives
+/// the current synced time from the monotonic time elapsed since, so it's immune to steps.
+///
+/// It also keeps track of the system clock, so it can report how far that was stepped since the
+/// anchoring with [`wall_step()`](SyncedClock::wall_step).
+///
+/// Create one right after a successful sync, with
+/// [`TimeSource::synced_clock()`](crate::TimeSource::synced_clock) or [`SyncedClock::new()`], and
+/// replace it after every sync. If you use a custom [`Clock`], read this with the `_with` methods
+/// and the same clock. Note that the monotonic clock and the system clock may drift apart.
+///
+/// The anchor is the [`Clock::boot_instant()`], which keeps counting while the system is
+/// suspended on Linux and Android. On other platforms the monotonic clock may not advance while
+/// suspended, and after a suspend this clock will be behind, and that will be reported as a step;
+/// supply a [`Clock`] with a [`boot_instant()`](Clock::boot_instant) that does to avoid that.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SyncedClock {
+    anchor: Instant,
+    wall_at_anchor: Timestamp,
+    offset: SignedDuration,
+}
+
+impl SyncedClock {
+    /// Anchor the given offset to the current boot instant.
+    ///
+    /// The offset must have been computed against the current system clock.
+    pub fn new(offset: SignedDuration) -> Self {
+        Self::with_clock(offset, &SystemClock)
+    }
+
+    /// Anchor the given offset to the current boot instant of a clock.
+    ///
+    /// The offset must have been computed against the clock.
+    pub fn with_clock(offset: SignedDuration, clock: &dyn Clock) -> Self {
+        Self::anchored(offset, clock.boot_instant(), clock.now())
+    }
+
+    pub(crate) fn anchored(offset: SignedDuration, anchor: Instant, wall: Timestamp) -> Self {
+        Self {
+            anchor,
+            wall_at_anchor: wall,
+            offset,
+        }
+    }
+
+    /// The offset this clock was anchored with.
+    pub fn offset(&self) -> SignedDuration {
+        self.offset
+    }
+
+    /// The current synced time.
+    ///
+    /// This is the system time at the anchoring, plus the offset, plus the monotonic time elapsed
+    /// since. It does not read the system clock.
+    pub fn now(&self) -> Timestamp {
+        self.now_with(&SystemClock)
+    }
+
+    /// The current synced time, reading the boot instant from a clock.
+    pub fn now_with(&self, clock: &dyn Clock) -> Timestamp {
+        self.wall_at_anchor + self.offset + clock.boot_instant().duration_since(self.anchor)
+    }
+
+    /// How far the system clock has been stepped since the anchoring.
+    ///
+    /// This is the difference between the current system time and what it would be if it had
+    /// advanced exactly as the monotonic clock did. Positive if the system clock was stepped
+    /// forward, negative if it was stepped backward.
+    ///
+    /// Besides steps, this also includes the drift between the two clocks, so you should compare
+    /// it against a threshold (of a few milliseconds, say) to decide whether the clock has been
+    /// stepped and a resync is needed.
+    pub fn wall_step(&self) -> SignedDuration {
+        self.wall_step_with(&SystemClock)
+    }
+
+    /// How far a clock has been stepped since the anchoring.
+    pub fn wall_step_with(&self, clock: &dyn Clock) -> SignedDuration {
+        let expected = self.wall_at_anchor + clock.boot_instant().duration_since(self.anchor);
+        (clock.now() - expected)
+            .to_duration(SpanRelativeTo::days_are_24_hours())
+            .unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn within(actual: SignedDuration, expected: SignedDuration) -> bool {
+        (actual - expected).unsigned_abs() < std::time::Duration::from_millis(5)
+    }
+
+    #[test]
+    fn applies_offset() {
+        let clock = SyncedClock::new(SignedDuration::from_secs(5));
+        let expected = Timestamp::now() + SignedDuration::from_secs(5);
+        let actual = clock.now();
+        assert!(
+            within(actual.duration_since(expected), SignedDuration::ZERO),
+            "{actual} != {expected}"
+        );
+        assert_eq!(clock.offset(), SignedDuration::from_secs(5));
+    }
+
+    #[test]
+    fn no_step() {
+        let clock = SyncedClock::new(SignedDuration::from_secs(-5));
+        let step = clock.wall_step();
+        assert!(within(step, SignedDuration::ZERO), "step = {step:?}");
+    }
+
+    #[test]
+    fn wall_stepped_forward() {
+        // as if the system clock was 10 seconds behind when anchored, then stepped forward
+        let clock = SyncedClock::anchored(
+            SignedDuration::from_secs(10),
+            SystemClock.boot_instant(),
+            Timestamp::now() - SignedDuration::from_secs(10),
+        );
+
+        let step = clock.wall_step();
+        assert!(
+            within(step, SignedDuration::from_secs(10)),
+            "step = {step:?}"
+        );
+
+        // synced time is still derived from the anchor, not the stepped system clock
+        let synced = clock.now().duration_since(Timestamp::now());
+        assert!(within(synced, SignedDuration::ZERO), "synced = {synced:?}");
+    }
+
+    #[test]
+    fn wall_stepped_backward() {
+        let clock = SyncedClock::anchored(
+            SignedDuration::ZERO,
+            SystemClock.boot_instant(),
+            Timestamp::now() + SignedDuration::from_secs(3),
+        );
+
+        let step = clock.wall_step();
+        assert!(
+            within(step, SignedDuration::from_secs(-3)),
+            "step = {step:?}"
+        );
+    }
+}
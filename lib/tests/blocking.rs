@@ -0,0 +1,189 @@
+#![allow(missing_docs)]
+#![cfg(feature = "blocking")]
+
+use std::{
+    sync::{
+        LazyLock,
+        atomic::{AtomicUsize, Ordering},
+    },
+    time::Duration,
+};
+
+use timesimp::{
+    History, Sample, SignedDuration, SyncError, SyncRecord,
+    blocking::{TimeSource, TimesimpClient, TimesimpServer},
+};
+
+static SETUP: LazyLock<()> = LazyLock::new(|| {
+    tracing_subscriber::fmt()
+        .with_max_level(tracing::Level::TRACE)
+        .init();
+});
+
+#[derive(Debug, Default)]
+struct ServerSimp {
+    offset: SignedDuration,
+}
+
+#[derive(Debug, thiserror::Error)]
+#[error("Test error")]
+struct TestError;
+
+impl TimeSource for ServerSimp {
+    type Err = TestError;
+
+    fn load_offset(&self) -> Result<Option<SignedDuration>, Self::Err> {
+        Ok(Some(self.offset))
+    }
+}
+
+impl TimesimpServer for ServerSimp {}
+
+#[derive(Debug, Default)]
+struct ClientSimp {
+    offset: Option<SignedDuration>,
+    record: Option<SyncRecord>,
+    history: Vec<Sample>,
+    server: ServerSimp,
+    down: bool,
+    slow: Duration,
+    queries: AtomicUsize,
+}
+
+impl TimeSource for ClientSimp {
+    type Err = TestError;
+
+    fn load_offset(&self) -> Result<Option<SignedDuration>, Self::Err> {
+        Ok(self.offset)
+    }
+
+    fn load_sync_record(&self) -> Result<Option<SyncRecord>, Self::Err> {
+        Ok(self.record)
+    }
+}
+
+impl TimesimpClient for ClientSimp {
+    fn store_offset(&mut self, offset: SignedDuration) -> Result<(), Self::Err> {
+        self.offset = Some(offset);
+        Ok(())
+    }
+
+    fn query_server(&self, request: timesimp::Request) -> Result<timesimp::Response, Self::Err> {
+        let query = self.queries.fetch_add(1, Ordering::Relaxed);
+        if self.down {
+            return Err(TestError);
+        }
+        if query % 2 == 1 {
+            // every other query is slow
+            std::thread::sleep(self.slow);
+        }
+        std::thread::sleep(Duration::from_millis(1));
+        let response = self.server.answer_client(request)?;
+        std::thread::sleep(Duration::from_millis(1));
+        Ok(response)
+    }
+
+    fn store_sync_record(&mut self, record: SyncRecord) -> Result<(), Self::Err> {
+        self.record = Some(record);
+        Ok(())
+    }
+
+    fn store_samples(&mut self, samples: Vec<Sample>) -> Result<(), Self::Err> {
+        self.history = samples;
+        Ok(())
+    }
+
+    fn load_samples(&self) -> Result<Vec<Sample>, Self::Err> {
+        Ok(self.history.clone())
+    }
+}
+
+fn settings() -> timesimp::Settings {
+    timesimp::Settings {
+        jitter: Duration::from_millis(10),
+        ..Default::default()
+    }
+}
+
+#[test]
+fn syncs() {
+    *SETUP;
+
+    let mut simp = ClientSimp {
+        server: ServerSimp {
+            offset: SignedDuration::from_secs(5),
+        },
+        ..Default::default()
+    };
+
+    let offset = simp.attempt_sync(settings()).unwrap() - SignedDuration::from_secs(5);
+    assert!(
+        offset > SignedDuration::from_millis(-5) && offset < SignedDuration::from_millis(5),
+        "offset - 5s = {offset:?}"
+    );
+    assert_eq!(simp.queries.load(Ordering::Relaxed), 5);
+    assert!(simp.adjusted_bounds(0).unwrap().is_some());
+}
+
+#[test]
+fn keeps_history() {
+    *SETUP;
+
+    let mut simp = ClientSimp::default();
+    let settings = timesimp::Settings {
+        history: Some(History {
+            window: Duration::from_secs(60),
+            max_samples: 100,
+        }),
+        ..settings()
+    };
+
+    simp.attempt_sync(settings).unwrap();
+    simp.attempt_sync(settings).unwrap();
+    assert_eq!(simp.history.len(), 10);
+}
+
+#[test]
+fn server_down() {
+    *SETUP;
+
+    let mut simp = ClientSimp {
+        down: true,
+        record: Some(SyncRecord {
+            at: timesimp::Timestamp::now(),
+            error: Duration::from_millis(1),
+            offset: SignedDuration::ZERO,
+            drift: None,
+            failed_attempts: 0,
+        }),
+        ..Default::default()
+    };
+
+    let err = simp.attempt_sync_report(settings()).unwrap_err();
+    assert!(
+        matches!(&err, SyncError::AllQueriesFailed { errors, timeouts: 0 } if errors.len() == 5),
+        "{err:?}"
+    );
+    assert_eq!(simp.offset, None);
+    assert!(simp.sync_state(0).unwrap().unwrap().in_holdover());
+}
+
+#[test]
+fn slow_queries_time_out() {
+    *SETUP;
+
+    let mut simp = ClientSimp {
+        slow: Duration::from_millis(50),
+        ..Default::default()
+    };
+
+    let report = simp
+        .attempt_sync_report(timesimp::Settings {
+            timeout: Some(Duration::from_millis(20)),
+            ..settings()
+        })
+        .unwrap();
+    assert_eq!(report.samples, 3);
+    assert_eq!(report.failures, 2);
+    assert_eq!(report.timeouts, 2);
+}
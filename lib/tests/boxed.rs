@@ -0,0 +1,137 @@
+#![allow(missing_docs)]
+
+use std::{sync::LazyLock, time::Duration};
+
+// all traits in scope, as with a glob import, to check their method names don't clash
+use timesimp::*;
+
+static SETUP: LazyLock<()> = LazyLock::new(|| {
+    tracing_subscriber::fmt()
+        .with_max_level(tracing::Level::TRACE)
+        .init();
+});
+
+#[derive(Debug, thiserror::Error)]
+#[error("Test error")]
+struct TestError;
+
+#[derive(Debug)]
+struct ServerSimp;
+
+impl TimeSource for ServerSimp {
+    type Err = TestError;
+
+    async fn load_offset(&self) -> Result<Option<SignedDuration>, Self::Err> {
+        Ok(Some(SignedDuration::from_secs(5)))
+    }
+}
+
+impl TimesimpServer for ServerSimp {}
+
+/// A client querying the server in-process.
+#[derive(Debug, Default)]
+struct LocalClient {
+    offset: Option<SignedDuration>,
+}
+
+impl TimeSource for LocalClient {
+    type Err = TestError;
+
+    async fn load_offset(&self) -> Result<Option<SignedDuration>, Self::Err> {
+        Ok(self.offset)
+    }
+}
+
+impl TimesimpClient for LocalClient {
+    async fn store_offset(&mut self, offset: SignedDuration) -> Result<(), Self::Err> {
+        self.offset = Some(offset);
+        Ok(())
+    }
+
+    async fn query_server(
+        &self,
+        request: timesimp::Request,
+    ) -> Result<timesimp::Response, Self::Err> {
+        ServerSimp.answer_client(request).await
+    }
+
+    async fn sleep(&self, duration: Duration) {
+        tokio::time::sleep(duration).await;
+    }
+}
+
+/// A client whose server can't be reached.
+#[derive(Debug)]
+struct DownClient;
+
+impl TimeSource for DownClient {
+    type Err = TestError;
+
+    async fn load_offset(&self) -> Result<Option<SignedDuration>, Self::Err> {
+        Ok(None)
+    }
+}
+
+impl TimesimpClient for DownClient {
+    async fn store_offset(&mut self, _offset: SignedDuration) -> Result<(), Self::Err> {
+        Ok(())
+    }
+
+    async fn query_server(
+        &self,
+        _request: timesimp::Request,
+    ) -> Result<timesimp::Response, Self::Err> {
+        Err(TestError)
+    }
+
+    async fn sleep(&self, duration: Duration) {
+        tokio::time::sleep(duration).await;
+    }
+}
+
+#[tokio::test]
+async fn heterogeneous_clients() {
+    *SETUP;
+
+    let mut clients: Vec<Box<dyn DynTimesimpClient<Err = TestError>>> =
+        vec![Box::new(LocalClient::default()), Box::new(DownClient)];
+
+    let settings = Settings {
+        samples: 5,
+        jitter: Duration::from_millis(10),
+        ..Default::default()
+    };
+
+    let offset = clients[0].attempt_sync(settings).await.unwrap() - SignedDuration::from_secs(5);
+    assert!(
+        offset > SignedDuration::from_millis(-5) && offset < SignedDuration::from_millis(5),
+        "offset - 5s = {offset:?}"
+    );
+    assert_eq!(
+        clients[0].load_offset().await.unwrap(),
+        Some(offset + SignedDuration::from_secs(5))
+    );
+
+    let err = clients[1].attempt_sync(settings).await.unwrap_err();
+    assert!(
+        matches!(err, SyncError::AllQueriesFailed { ref errors, .. } if errors.len() == 5),
+        "{err:?}"
+    );
+}
+
+#[tokio::test]
+async fn plain_client_with_all_traits_in_scope() {
+    *SETUP;
+
+    let mut client = LocalClient::default();
+    let offset = client
+        .attempt_sync(Settings {
+            samples: 5,
+            jitter: Duration::from_millis(10),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+    assert_eq!(client.load_offset().await.unwrap(), Some(offset));
+    assert!(client.adjusted_timestamp().await.is_ok());
+}
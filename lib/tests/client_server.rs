@@ -2,11 +2,14 @@
 
 use std::{
     sync::{Arc, LazyLock},
-    time::Duration,
+    time::{Duration, Instant},
 };
 
 use rand::random_range;
-use timesimp::{SignedDuration, Timesimp};
+use timesimp::{
+    Clock, SignedDuration, SyncError, SyncRecord, TimeSource, TimesimpClient, TimesimpServer,
+    Timestamp,
+};
 use tokio::time::sleep;
 
 static SETUP: LazyLock<()> = LazyLock::new(|| {
@@ -18,6 +21,9 @@ static SETUP: LazyLock<()> = LazyLock::new(|| {
 #[derive(Debug, Default)]
 struct ClientSimp {
     offset: Option<SignedDuration>,
+    record: Option<SyncRecord>,
+    clock: ShiftedClock,
+    down: bool,
     delay: Duration,
     jitter_percent: u8,
     server: Arc<ServerSimp>,
@@ -28,17 +34,41 @@ struct ServerSimp {
     offset: Option<SignedDuration>,
 }
 
+/// The system clock, shifted by some amount.
+#[derive(Debug, Default)]
+struct ShiftedClock(SignedDuration);
+
+impl Clock for ShiftedClock {
+    fn now(&self) -> Timestamp {
+        Timestamp::now() + self.0
+    }
+
+    fn instant(&self) -> Instant {
+        Instant::now()
+    }
+}
+
 #[derive(Debug, thiserror::Error)]
 #[error("Test error")]
 struct TestError;
 
-impl Timesimp for ClientSimp {
+impl TimeSource for ClientSimp {
     type Err = TestError;
 
     async fn load_offset(&self) -> Result<Option<SignedDuration>, Self::Err> {
         Ok(self.offset)
     }
 
+    async fn load_sync_record(&self) -> Result<Option<SyncRecord>, Self::Err> {
+        Ok(self.record)
+    }
+
+    fn clock(&self) -> &dyn Clock {
+        &self.clock
+    }
+}
+
+impl TimesimpClient for ClientSimp {
     async fn store_offset(&mut self, offset: SignedDuration) -> Result<(), Self::Err> {
         self.offset = Some(offset);
         Ok(())
@@ -48,6 +78,10 @@ impl Timesimp for ClientSimp {
         &self,
         request: timesimp::Request,
     ) -> Result<timesimp::Response, Self::Err> {
+        if self.down {
+            return Err(TestError);
+        }
+
         let delay = (self.delay / 2).as_nanos() as f64;
         let jitter = random_range(0.0..=(self.jitter_percent as f64)) / 100.0;
         let delay = Duration::from_nanos((delay * (1.0 - jitter)) as u64);
@@ -58,34 +92,26 @@ impl Timesimp for ClientSimp {
         res
     }
 
-    async fn sleep(duration: std::time::Duration) {
+    async fn sleep(&self, duration: std::time::Duration) {
         tokio::time::sleep(duration).await;
     }
+
+    async fn store_sync_record(&mut self, record: SyncRecord) -> Result<(), Self::Err> {
+        self.record = Some(record);
+        Ok(())
+    }
 }
 
-impl Timesimp for ServerSimp {
+impl TimeSource for ServerSimp {
     type Err = TestError;
 
     async fn load_offset(&self) -> Result<Option<SignedDuration>, Self::Err> {
         Ok(self.offset)
     }
-
-    async fn store_offset(&mut self, _offset: SignedDuration) -> Result<(), Self::Err> {
-        unimplemented!()
-    }
-
-    async fn query_server(
-        &self,
-        _request: timesimp::Request,
-    ) -> Result<timesimp::Response, Self::Err> {
-        unimplemented!()
-    }
-
-    async fn sleep(duration: std::time::Duration) {
-        tokio::time::sleep(duration).await;
-    }
 }
 
+impl TimesimpServer for ServerSimp {}
+
 #[tokio::test]
 async fn no_delay() {
     *SETUP;
@@ -102,8 +128,7 @@ async fn no_delay() {
         .await
         .unwrap();
     assert!(
-        offset.unwrap() > SignedDuration::from_millis(-5)
-            && offset.unwrap() < SignedDuration::from_millis(5),
+        offset > SignedDuration::from_millis(-5) && offset < SignedDuration::from_millis(5),
         "offset = {offset:?}"
     );
 }
@@ -125,8 +150,7 @@ async fn some_delay() {
         .await
         .unwrap();
     assert!(
-        offset.unwrap() > SignedDuration::from_millis(-5)
-            && offset.unwrap() < SignedDuration::from_millis(5),
+        offset > SignedDuration::from_millis(-5) && offset < SignedDuration::from_millis(5),
         "offset = {offset:?}"
     );
 }
@@ -149,8 +173,7 @@ async fn much_delay() {
         .await
         .unwrap();
     assert!(
-        offset.unwrap() > SignedDuration::from_millis(-5)
-            && offset.unwrap() < SignedDuration::from_millis(5),
+        offset > SignedDuration::from_millis(-5) && offset < SignedDuration::from_millis(5),
         "offset = {offset:?}"
     );
 }
@@ -173,7 +196,6 @@ async fn server_offset_positive() {
         .attempt_sync(timesimp::Settings::default())
         .await
         .unwrap()
-        .unwrap()
         - SignedDuration::from_secs(5);
     assert!(
         offset > SignedDuration::from_millis(-5) && offset < SignedDuration::from_millis(5),
@@ -199,7 +221,6 @@ async fn server_offset_negative() {
         .attempt_sync(timesimp::Settings::default())
         .await
         .unwrap()
-        .unwrap()
         + SignedDuration::from_secs(5);
     assert!(
         offset > SignedDuration::from_millis(-5) && offset < SignedDuration::from_millis(5),
@@ -223,7 +244,6 @@ async fn client_offset_positive() {
     let offset = client
         .attempt_sync(timesimp::Settings::default())
         .await
-        .unwrap()
         .unwrap();
     assert!(
         offset > SignedDuration::from_millis(-5) && offset < SignedDuration::from_millis(5),
@@ -247,7 +267,6 @@ async fn client_offset_negative() {
     let offset = client
         .attempt_sync(timesimp::Settings::default())
         .await
-        .unwrap()
         .unwrap();
     assert!(
         offset > SignedDuration::from_millis(-5) && offset < SignedDuration::from_millis(5),
@@ -274,7 +293,6 @@ async fn low_jitter() {
         .attempt_sync(timesimp::Settings::default())
         .await
         .unwrap()
-        .unwrap()
         - SignedDuration::from_secs(5);
     assert!(
         offset > SignedDuration::from_millis(-5) && offset < SignedDuration::from_millis(5),
@@ -301,7 +319,6 @@ async fn mid_jitter() {
         .attempt_sync(timesimp::Settings::default())
         .await
         .unwrap()
-        .unwrap()
         - SignedDuration::from_secs(5);
     assert!(
         offset > SignedDuration::from_millis(-5) && offset < SignedDuration::from_millis(5),
@@ -328,10 +345,304 @@ async fn high_jitter() {
         .attempt_sync(timesimp::Settings::default())
         .await
         .unwrap()
-        .unwrap()
         - SignedDuration::from_secs(5);
     assert!(
         offset > SignedDuration::from_millis(-5) && offset < SignedDuration::from_millis(5),
         "offset - 5s = {offset:?}"
     );
 }
+
+#[tokio::test]
+async fn offset_too_large() {
+    *SETUP;
+
+    let server = Arc::new(ServerSimp {
+        offset: Some(SignedDuration::from_hours(-24 * 365 * 50)),
+    });
+
+    let mut client = ClientSimp {
+        server,
+        ..Default::default()
+    };
+
+    let err = client
+        .attempt_sync(timesimp::Settings {
+            jitter: Duration::from_millis(10),
+            max_offset: Some(Duration::from_secs(3600)),
+            ..Default::default()
+        })
+        .await
+        .unwrap_err();
+    assert!(
+        matches!(err, timesimp::SyncError::OffsetTooLarge { .. }),
+        "err = {err:?}"
+    );
+    assert_eq!(client.offset, None, "initial delta must not be stored");
+}
+
+#[tokio::test]
+async fn first_sync_may_step() {
+    *SETUP;
+
+    let server = Arc::new(ServerSimp {
+        offset: Some(SignedDuration::from_hours(2)),
+    });
+
+    let mut client = ClientSimp {
+        server,
+        ..Default::default()
+    };
+
+    let settings = timesimp::Settings {
+        jitter: Duration::from_millis(10),
+        max_offset: Some(Duration::from_secs(3600)),
+        first_sync_may_step: true,
+        ..Default::default()
+    };
+
+    let offset = client.attempt_sync(settings).await.unwrap() - SignedDuration::from_hours(2);
+    assert!(
+        offset > SignedDuration::from_millis(-5) && offset < SignedDuration::from_millis(5),
+        "offset - 2h = {offset:?}"
+    );
+
+    // once synced, the limit applies
+    client.offset = Some(SignedDuration::from_secs(1));
+    let err = client.attempt_sync(settings).await.unwrap_err();
+    assert!(
+        matches!(err, timesimp::SyncError::OffsetTooLarge { .. }),
+        "err = {err:?}"
+    );
+    assert_eq!(client.offset, Some(SignedDuration::from_secs(1)));
+}
+
+#[tokio::test]
+async fn change_too_large() {
+    *SETUP;
+
+    let server = Arc::new(ServerSimp {
+        offset: Some(SignedDuration::from_secs(5)),
+    });
+
+    let mut client = ClientSimp {
+        offset: Some(SignedDuration::from_secs(1)),
+        server,
+        ..Default::default()
+    };
+
+    let settings = timesimp::Settings {
+        jitter: Duration::from_millis(10),
+        max_change: Some(Duration::from_secs(1)),
+        ..Default::default()
+    };
+
+    let err = client.attempt_sync(settings).await.unwrap_err();
+    assert!(
+        matches!(err, timesimp::SyncError::ChangeTooLarge { .. }),
+        "err = {err:?}"
+    );
+    assert_eq!(client.offset, Some(SignedDuration::from_secs(1)));
+
+    // a small enough change is stored
+    client.offset = Some(SignedDuration::from_millis(4500));
+    let offset = client.attempt_sync(settings).await.unwrap() - SignedDuration::from_secs(5);
+    assert!(
+        offset > SignedDuration::from_millis(-5) && offset < SignedDuration::from_millis(5),
+        "offset - 5s = {offset:?}"
+    );
+}
+
+#[tokio::test]
+async fn adjusted_bounds() {
+    *SETUP;
+
+    let server = Arc::new(ServerSimp {
+        offset: Some(SignedDuration::from_secs(5)),
+    });
+
+    let mut client = ClientSimp {
+        delay: Duration::from_millis(20),
+        server,
+        ..Default::default()
+    };
+
+    assert_eq!(client.adjusted_bounds(100_000).await.unwrap(), None);
+
+    let report = client
+        .attempt_sync_report(timesimp::Settings {
+            jitter: Duration::from_millis(10),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+    let error = report.error;
+    assert!(
+        error >= Duration::from_millis(10) && error < Duration::from_millis(20),
+        "error = {error:?}"
+    );
+    assert_eq!(client.record.unwrap().error, error);
+
+    let bounds = client.adjusted_bounds(100_000).await.unwrap().unwrap();
+    let server_time = Timestamp::now() + SignedDuration::from_secs(5);
+    assert!(
+        bounds.earliest <= server_time && server_time <= bounds.latest,
+        "{server_time} not within {bounds:?}"
+    );
+}
+
+#[tokio::test]
+async fn wait_until_after() {
+    *SETUP;
+
+    let server = Arc::new(ServerSimp {
+        offset: Some(SignedDuration::from_secs(5)),
+    });
+
+    let mut client = ClientSimp {
+        delay: Duration::from_millis(20),
+        server,
+        ..Default::default()
+    };
+
+    client
+        .attempt_sync(timesimp::Settings {
+            jitter: Duration::from_millis(10),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+
+    let target = Timestamp::now() + SignedDuration::from_secs(5) + SignedDuration::from_millis(100);
+    let bounds = client
+        .wait_until_after(target, 100_000)
+        .await
+        .unwrap()
+        .unwrap();
+    assert!(bounds.is_after(target), "{bounds:?}");
+
+    let server_time = Timestamp::now() + SignedDuration::from_secs(5);
+    assert!(server_time > target, "{server_time} <= {target}");
+}
+
+#[tokio::test]
+async fn custom_clock() {
+    *SETUP;
+
+    let server = Arc::new(ServerSimp::default());
+
+    // as if the client read TAI
+    let mut client = ClientSimp {
+        clock: ShiftedClock(SignedDuration::from_secs(37)),
+        server,
+        ..Default::default()
+    };
+
+    let offset = client
+        .attempt_sync(timesimp::Settings {
+            jitter: Duration::from_millis(10),
+            ..Default::default()
+        })
+        .await
+        .unwrap()
+        + SignedDuration::from_secs(37);
+    assert!(
+        offset > SignedDuration::from_millis(-5) && offset < SignedDuration::from_millis(5),
+        "offset + 37s = {offset:?}"
+    );
+
+    let adjusted = client
+        .adjusted_timestamp()
+        .await
+        .unwrap()
+        .duration_since(Timestamp::now());
+    assert!(
+        adjusted > SignedDuration::from_millis(-5) && adjusted < SignedDuration::from_millis(5),
+        "adjusted - now = {adjusted:?}"
+    );
+}
+
+#[tokio::test]
+async fn holdover() {
+    *SETUP;
+
+    let server = Arc::new(ServerSimp {
+        offset: Some(SignedDuration::from_secs(5)),
+    });
+
+    let mut client = ClientSimp {
+        delay: Duration::from_millis(10),
+        server,
+        ..Default::default()
+    };
+
+    let settings = timesimp::Settings {
+        jitter: Duration::from_millis(10),
+        ..Default::default()
+    };
+
+    assert_eq!(client.sync_state(100_000).await.unwrap(), None);
+
+    client.attempt_sync(settings).await.unwrap();
+    let synced = client.sync_state(100_000).await.unwrap().unwrap();
+    assert!(!synced.in_holdover(), "{synced:?}");
+    assert_eq!(synced.drift, None);
+
+    // as if the last sync was long ago, and the local clock was found to drift
+    let record = client.record.as_mut().unwrap();
+    record.at -= SignedDuration::from_hours(1);
+    record.drift = Some(10_000);
+
+    client.down = true;
+    for _ in 0..2 {
+        let err = client.attempt_sync(settings).await.unwrap_err();
+        assert!(
+            matches!(&err, SyncError::AllQueriesFailed { errors, timeouts: 0 } if errors.len() == 5),
+            "{err:?}"
+        );
+    }
+
+    let held = client.sync_state(100_000).await.unwrap().unwrap();
+    assert!(held.in_holdover(), "{held:?}");
+    assert_eq!(held.failed_attempts, 2);
+    assert!(
+        held.since_sync >= Duration::from_secs(3600),
+        "since_sync = {:?}",
+        held.since_sync
+    );
+
+    // 10ppm over an hour is 36ms
+    let extrapolated = held.offset - synced.offset;
+    assert!(
+        extrapolated > SignedDuration::from_millis(35)
+            && extrapolated < SignedDuration::from_millis(37),
+        "extrapolated = {extrapolated:?}"
+    );
+
+    // the adjusted time follows the extrapolated offset, not the stored one
+    let stored = client.adjusted_timestamp().await.unwrap();
+    let timestamp = client.extrapolated_timestamp().await.unwrap();
+    let ahead = timestamp.duration_since(stored);
+    assert!(
+        ahead > SignedDuration::from_millis(35) && ahead < SignedDuration::from_millis(37),
+        "extrapolated - stored = {ahead:?}"
+    );
+    let bounds = client.adjusted_bounds(100_000).await.unwrap().unwrap();
+    let centre = bounds.midpoint().duration_since(timestamp);
+    assert!(
+        centre > SignedDuration::from_millis(-1) && centre < SignedDuration::from_millis(1),
+        "midpoint - extrapolated = {centre:?}"
+    );
+
+    // 100ppm over an hour is 360ms
+    assert!(
+        held.error > synced.error + Duration::from_millis(359),
+        "error = {:?}",
+        held.error
+    );
+
+    // a good sync ends the holdover
+    client.down = false;
+    client.attempt_sync(settings).await.unwrap();
+    let resynced = client.sync_state(100_000).await.unwrap().unwrap();
+    assert!(!resynced.in_holdover(), "{resynced:?}");
+}
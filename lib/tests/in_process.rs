@@ -1,8 +1,18 @@
 #![allow(missing_docs)]
 
-use std::sync::LazyLock;
+use std::{
+    sync::{
+        LazyLock,
+        atomic::{AtomicUsize, Ordering},
+    },
+    time::Duration,
+};
 
-use timesimp::{SignedDuration, Timesimp};
+use rand::random_range;
+use timesimp::{
+    Adaptive, Asymmetry, History, Sample, SignedDuration, SyncError, TimeSource, TimesimpClient,
+    TimesimpServer, Timestamp,
+};
 
 static SETUP: LazyLock<()> = LazyLock::new(|| {
     tracing_subscriber::fmt()
@@ -13,19 +23,26 @@ static SETUP: LazyLock<()> = LazyLock::new(|| {
 #[derive(Debug, Default)]
 struct TestSimp {
     offset: Option<SignedDuration>,
+    noise: Duration,
+    echo: bool,
+    delay: Duration,
+    queries: AtomicUsize,
+    history: Vec<Sample>,
 }
 
 #[derive(Debug, thiserror::Error)]
 #[error("Test error")]
 struct TestError;
 
-impl Timesimp for TestSimp {
+impl TimeSource for TestSimp {
     type Err = TestError;
 
     async fn load_offset(&self) -> Result<Option<SignedDuration>, Self::Err> {
         Ok(self.offset)
     }
+}
 
+impl TimesimpClient for TestSimp {
     async fn store_offset(&mut self, offset: SignedDuration) -> Result<(), Self::Err> {
         self.offset = Some(offset);
         Ok(())
@@ -35,14 +52,45 @@ impl Timesimp for TestSimp {
         &self,
         request: timesimp::Request,
     ) -> Result<timesimp::Response, Self::Err> {
-        self.answer_client(request).await
+        self.queries.fetch_add(1, Ordering::Relaxed);
+        if !self.delay.is_zero() {
+            tokio::time::sleep(self.delay / 2).await;
+        }
+        let mut response = if self.echo {
+            // a server whose clock matches ours exactly, and stamps the request as it is sent
+            timesimp::Response {
+                client: request.client,
+                server: request.client,
+            }
+        } else {
+            self.answer_client(request).await?
+        };
+        if !self.delay.is_zero() {
+            tokio::time::sleep(self.delay / 2).await;
+        }
+        if !self.noise.is_zero() {
+            let noise = self.noise.as_nanos() as i64;
+            response.server += SignedDuration::from_nanos(random_range(-noise..=noise));
+        }
+        Ok(response)
     }
 
-    async fn sleep(duration: std::time::Duration) {
+    async fn sleep(&self, duration: std::time::Duration) {
         tokio::time::sleep(duration).await;
     }
+
+    async fn store_samples(&mut self, samples: Vec<Sample>) -> Result<(), Self::Err> {
+        self.history = samples;
+        Ok(())
+    }
+
+    async fn load_samples(&self) -> Result<Vec<Sample>, Self::Err> {
+        Ok(self.history.clone())
+    }
 }
 
+impl TimesimpServer for TestSimp {}
+
 #[tokio::test]
 async fn null_offset() {
     *SETUP;
@@ -54,8 +102,7 @@ async fn null_offset() {
         .await
         .unwrap();
     assert!(
-        offset.unwrap() > SignedDuration::from_millis(-1)
-            && offset.unwrap() < SignedDuration::from_millis(1),
+        offset > SignedDuration::from_millis(-1) && offset < SignedDuration::from_millis(1),
         "offset = {offset:?}"
     );
 }
@@ -66,6 +113,7 @@ async fn zero_offset() {
 
     let mut simp = TestSimp {
         offset: Some(SignedDuration::from_micros(0)),
+        ..Default::default()
     };
 
     let offset = simp
@@ -73,8 +121,7 @@ async fn zero_offset() {
         .await
         .unwrap();
     assert!(
-        offset.unwrap() > SignedDuration::from_millis(-1)
-            && offset.unwrap() < SignedDuration::from_millis(1),
+        offset > SignedDuration::from_millis(-1) && offset < SignedDuration::from_millis(1),
         "offset = {offset:?}"
     );
 }
@@ -85,13 +132,13 @@ async fn negative_starting_offset() {
 
     let mut simp = TestSimp {
         offset: Some(SignedDuration::from_secs(-5)),
+        ..Default::default()
     };
 
     let offset = simp
         .attempt_sync(timesimp::Settings::default())
         .await
         .unwrap()
-        .unwrap()
         + SignedDuration::from_secs(5);
     assert!(
         offset > SignedDuration::from_millis(-1) && offset < SignedDuration::from_millis(1),
@@ -105,16 +152,207 @@ async fn positive_starting_offset() {
 
     let mut simp = TestSimp {
         offset: Some(SignedDuration::from_secs(5)),
+        ..Default::default()
     };
 
     let offset = simp
         .attempt_sync(timesimp::Settings::default())
         .await
         .unwrap()
-        .unwrap()
         - SignedDuration::from_secs(5);
     assert!(
         offset > SignedDuration::from_millis(-1) && offset < SignedDuration::from_millis(1),
         "offset - 5s = {offset:?}"
     );
 }
+
+#[tokio::test]
+async fn adaptive_stops_early_when_stable() {
+    *SETUP;
+
+    let mut simp = TestSimp {
+        echo: true,
+        ..Default::default()
+    };
+
+    // with the whole round trip on the return leg, every delta is exactly zero
+    let offset = simp
+        .attempt_sync(timesimp::Settings {
+            samples: 3,
+            jitter: Duration::from_millis(10),
+            asymmetry: Asymmetry::Ratio {
+                outbound: 0,
+                inbound: 1,
+            },
+            adaptive: Some(Adaptive {
+                target: Duration::from_millis(1),
+                max_samples: 51,
+            }),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+    assert_eq!(offset, SignedDuration::ZERO);
+    assert_eq!(simp.queries.load(Ordering::Relaxed), 3);
+}
+
+#[tokio::test]
+async fn adaptive_keeps_sampling_when_noisy() {
+    *SETUP;
+
+    // start from a stored offset, so the server's answers aren't shifted by the first noisy delta
+    let mut simp = TestSimp {
+        offset: Some(SignedDuration::ZERO),
+        noise: Duration::from_millis(50),
+        ..Default::default()
+    };
+
+    let offset = simp
+        .attempt_sync(timesimp::Settings {
+            samples: 3,
+            jitter: Duration::from_millis(10),
+            adaptive: Some(Adaptive {
+                target: Duration::from_nanos(1),
+                max_samples: 21,
+            }),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+    assert!(
+        offset > SignedDuration::from_millis(-50) && offset < SignedDuration::from_millis(50),
+        "offset = {offset:?}"
+    );
+    assert_eq!(simp.queries.load(Ordering::Relaxed), 21);
+}
+
+#[tokio::test]
+async fn timeouts_count_as_failures() {
+    *SETUP;
+
+    let mut simp = TestSimp {
+        delay: Duration::from_millis(200),
+        ..Default::default()
+    };
+
+    let err = simp
+        .attempt_sync_report(timesimp::Settings {
+            samples: 3,
+            jitter: Duration::from_millis(10),
+            timeout: Some(Duration::from_millis(20)),
+            ..Default::default()
+        })
+        .await
+        .unwrap_err();
+    assert!(
+        matches!(&err, SyncError::AllQueriesFailed { errors, timeouts: 3 } if errors.is_empty()),
+        "{err:?}"
+    );
+    assert_eq!(simp.offset, None);
+}
+
+#[tokio::test]
+async fn timeout_not_reached() {
+    *SETUP;
+
+    let mut simp = TestSimp {
+        delay: Duration::from_millis(10),
+        ..Default::default()
+    };
+
+    let report = simp
+        .attempt_sync_report(timesimp::Settings {
+            samples: 3,
+            jitter: Duration::from_millis(10),
+            timeout: Some(Duration::from_secs(1)),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+    assert_eq!(report.samples, 3);
+    assert_eq!(report.failures, 0);
+    assert_eq!(report.timeouts, 0);
+}
+
+fn history_settings(max_samples: u16) -> timesimp::Settings {
+    timesimp::Settings {
+        samples: 3,
+        jitter: Duration::from_millis(10),
+        history: Some(History {
+            window: Duration::from_secs(3600),
+            max_samples,
+        }),
+        ..Default::default()
+    }
+}
+
+#[tokio::test]
+async fn history_accumulates() {
+    *SETUP;
+
+    let mut simp = TestSimp::default();
+
+    simp.attempt_sync(history_settings(7)).await.unwrap();
+    assert_eq!(simp.history.len(), 3);
+    simp.attempt_sync(history_settings(7)).await.unwrap();
+    assert_eq!(simp.history.len(), 6);
+    simp.attempt_sync(history_settings(7)).await.unwrap();
+    assert_eq!(simp.history.len(), 7);
+    assert!(simp.history.is_sorted_by_key(|sample| sample.at));
+}
+
+#[tokio::test]
+async fn history_survives_restart() {
+    *SETUP;
+
+    // as if restarted with history from a previous run, where the offset was 1ms
+    let now = Timestamp::now();
+    let history = (1..=6)
+        .map(|n| Sample {
+            at: now - SignedDuration::from_secs(n),
+            latency: Duration::from_micros(100),
+            delta: SignedDuration::from_millis(1),
+        })
+        .collect();
+    let mut simp = TestSimp {
+        offset: Some(SignedDuration::ZERO),
+        history,
+        ..Default::default()
+    };
+
+    let report = simp
+        .attempt_sync_report(history_settings(50))
+        .await
+        .unwrap();
+    assert_eq!(report.samples, 3);
+    assert_eq!(simp.history.len(), 9);
+
+    // the estimate comes from the whole window, not only the new samples at zero
+    let offset = report.offset;
+    assert!(
+        offset > SignedDuration::from_micros(500),
+        "offset = {offset:?}"
+    );
+}
+
+#[tokio::test]
+async fn history_window_expires() {
+    *SETUP;
+
+    let stale = Sample {
+        at: Timestamp::now() - SignedDuration::from_hours(2),
+        latency: Duration::from_micros(100),
+        delta: SignedDuration::from_secs(10),
+    };
+    let mut simp = TestSimp {
+        history: vec![stale; 10],
+        ..Default::default()
+    };
+
+    let offset = simp.attempt_sync(history_settings(50)).await.unwrap();
+    assert!(
+        offset > SignedDuration::from_millis(-1) && offset < SignedDuration::from_millis(1),
+        "offset = {offset:?}"
+    );
+    assert_eq!(simp.history.len(), 3);
+}
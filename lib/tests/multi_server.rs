@@ -0,0 +1,250 @@
+#![allow(missing_docs)]
+
+use std::{sync::LazyLock, time::Duration};
+
+use timesimp::{
+    MultiTimesimp, SignedDuration, SyncError, TimeSource, TimesimpClient, TimesimpServer,
+};
+
+static SETUP: LazyLock<()> = LazyLock::new(|| {
+    tracing_subscriber::fmt()
+        .with_max_level(tracing::Level::TRACE)
+        .init();
+});
+
+#[derive(Debug, Default)]
+struct ServerSimp {
+    offset: Option<SignedDuration>,
+    down: bool,
+}
+
+#[derive(Debug, Default)]
+struct ClientSimp {
+    offset: Option<SignedDuration>,
+    servers: Vec<ServerSimp>,
+}
+
+#[derive(Debug, thiserror::Error)]
+#[error("Test error")]
+struct TestError;
+
+impl TimeSource for ServerSimp {
+    type Err = TestError;
+
+    async fn load_offset(&self) -> Result<Option<SignedDuration>, Self::Err> {
+        Ok(self.offset)
+    }
+}
+
+impl TimesimpServer for ServerSimp {}
+
+impl TimeSource for ClientSimp {
+    type Err = TestError;
+
+    async fn load_offset(&self) -> Result<Option<SignedDuration>, Self::Err> {
+        Ok(self.offset)
+    }
+}
+
+impl TimesimpClient for ClientSimp {
+    async fn store_offset(&mut self, offset: SignedDuration) -> Result<(), Self::Err> {
+        self.offset = Some(offset);
+        Ok(())
+    }
+
+    async fn query_server(
+        &self,
+        request: timesimp::Request,
+    ) -> Result<timesimp::Response, Self::Err> {
+        self.query_server_at(0, request).await
+    }
+
+    async fn sleep(&self, duration: std::time::Duration) {
+        tokio::time::sleep(duration).await;
+    }
+}
+
+impl MultiTimesimp for ClientSimp {
+    fn servers(&self) -> usize {
+        self.servers.len()
+    }
+
+    async fn query_server_at(
+        &self,
+        server: usize,
+        request: timesimp::Request,
+    ) -> Result<timesimp::Response, Self::Err> {
+        let server = &self.servers[server];
+        if server.down {
+            return Err(TestError);
+        }
+
+        // a little latency, so each server's confidence interval isn't just scheduling noise
+        tokio::time::sleep(Duration::from_millis(1)).await;
+        let response = server.answer_client(request).await;
+        tokio::time::sleep(Duration::from_millis(1)).await;
+        response
+    }
+}
+
+fn settings() -> timesimp::Settings {
+    timesimp::Settings {
+        jitter: Duration::from_millis(10),
+        ..Default::default()
+    }
+}
+
+#[tokio::test]
+async fn all_agree() {
+    *SETUP;
+
+    let mut client = ClientSimp {
+        servers: vec![
+            ServerSimp::default(),
+            ServerSimp::default(),
+            ServerSimp::default(),
+        ],
+        ..Default::default()
+    };
+
+    let sync = client.attempt_multi_sync(settings()).await.unwrap();
+    assert_eq!(sync.truechimers.len(), 3, "{sync:?}");
+    assert!(sync.falsetickers.is_empty(), "{sync:?}");
+    assert!(sync.unreachable.is_empty(), "{sync:?}");
+
+    let offset = sync.offset;
+    assert!(
+        offset > SignedDuration::from_millis(-5) && offset < SignedDuration::from_millis(5),
+        "offset = {offset:?}"
+    );
+    assert_eq!(client.offset, Some(offset));
+}
+
+#[tokio::test]
+async fn rejects_falseticker() {
+    *SETUP;
+
+    let mut client = ClientSimp {
+        servers: vec![
+            ServerSimp {
+                offset: Some(SignedDuration::from_secs(5)),
+                ..Default::default()
+            },
+            ServerSimp {
+                offset: Some(SignedDuration::from_hours(-24 * 365 * 50)),
+                ..Default::default()
+            },
+            ServerSimp {
+                offset: Some(SignedDuration::from_secs(5)),
+                ..Default::default()
+            },
+        ],
+        ..Default::default()
+    };
+
+    let sync = client.attempt_multi_sync(settings()).await.unwrap();
+    assert_eq!(
+        sync.falsetickers
+            .iter()
+            .map(|est| est.server)
+            .collect::<Vec<_>>(),
+        vec![1],
+        "{sync:?}"
+    );
+
+    let offset = sync.offset - SignedDuration::from_secs(5);
+    assert!(
+        offset > SignedDuration::from_millis(-5) && offset < SignedDuration::from_millis(5),
+        "offset - 5s = {offset:?}"
+    );
+}
+
+#[tokio::test]
+async fn unreachable_server() {
+    *SETUP;
+
+    let mut client = ClientSimp {
+        servers: vec![
+            ServerSimp::default(),
+            ServerSimp {
+                down: true,
+                ..Default::default()
+            },
+            ServerSimp::default(),
+        ],
+        ..Default::default()
+    };
+
+    let sync = client.attempt_multi_sync(settings()).await.unwrap();
+    assert_eq!(sync.unreachable, vec![1], "{sync:?}");
+    assert_eq!(sync.truechimers.len(), 2, "{sync:?}");
+    assert_eq!(client.offset, Some(sync.offset));
+}
+
+#[tokio::test]
+async fn most_servers_down() {
+    *SETUP;
+
+    let mut client = ClientSimp {
+        servers: vec![
+            ServerSimp {
+                offset: Some(SignedDuration::from_secs(60)),
+                ..Default::default()
+            },
+            ServerSimp {
+                down: true,
+                ..Default::default()
+            },
+            ServerSimp {
+                down: true,
+                ..Default::default()
+            },
+        ],
+        ..Default::default()
+    };
+
+    let err = client.attempt_multi_sync(settings()).await.unwrap_err();
+    let SyncError::NoMajority {
+        servers,
+        falsetickers,
+        unreachable,
+        errors,
+    } = err
+    else {
+        panic!("{err:?}");
+    };
+    assert_eq!(servers, 3);
+    assert_eq!(falsetickers.len(), 1);
+    assert_eq!(unreachable, vec![1, 2]);
+    assert_eq!(
+        errors.iter().map(Vec::len).collect::<Vec<_>>(),
+        vec![0, 5, 5]
+    );
+    assert_eq!(
+        client.offset, None,
+        "the remaining server is not trusted alone"
+    );
+}
+
+#[tokio::test]
+async fn no_majority() {
+    *SETUP;
+
+    let mut client = ClientSimp {
+        servers: vec![
+            ServerSimp::default(),
+            ServerSimp {
+                offset: Some(SignedDuration::from_secs(60)),
+                ..Default::default()
+            },
+        ],
+        ..Default::default()
+    };
+
+    let err = client.attempt_multi_sync(settings()).await.unwrap_err();
+    assert!(
+        matches!(err, SyncError::NoMajority { ref falsetickers, .. } if falsetickers.len() == 2),
+        "{err:?}"
+    );
+    assert_eq!(client.offset, None);
+}
@@ -0,0 +1,182 @@
+#![allow(missing_docs)]
+
+use std::{
+    sync::{Arc, LazyLock, Mutex},
+    time::Duration,
+};
+
+// the async traits are in scope too, to check the method names don't clash
+use timesimp::{
+    SendTimeSource, SendTimesimpClient, SendTimesimpServer, Settings, SignedDuration, SyncRecord,
+    TimeBounds, TimeSource, TimesimpClient, Timestamp,
+};
+
+static SETUP: LazyLock<()> = LazyLock::new(|| {
+    tracing_subscriber::fmt()
+        .with_max_level(tracing::Level::TRACE)
+        .init();
+});
+
+#[derive(Debug, thiserror::Error)]
+#[error("Test error")]
+struct TestError;
+
+#[derive(Debug)]
+struct ServerSimp;
+
+impl SendTimeSource for ServerSimp {
+    type Err = TestError;
+
+    async fn load_offset(&self) -> Result<Option<SignedDuration>, Self::Err> {
+        Ok(Some(SignedDuration::from_secs(5)))
+    }
+}
+
+impl SendTimesimpServer for ServerSimp {}
+
+/// Storage shared with the test, as the client is moved into the spawned task.
+#[derive(Debug, Default)]
+struct Storage {
+    offset: Option<SignedDuration>,
+    record: Option<SyncRecord>,
+}
+
+#[derive(Debug)]
+struct ClientSimp {
+    storage: Arc<Mutex<Storage>>,
+    server: Arc<ServerSimp>,
+}
+
+impl SendTimeSource for ClientSimp {
+    type Err = TestError;
+
+    async fn load_offset(&self) -> Result<Option<SignedDuration>, Self::Err> {
+        Ok(self.storage.lock().unwrap().offset)
+    }
+
+    async fn load_sync_record(&self) -> Result<Option<SyncRecord>, Self::Err> {
+        Ok(self.storage.lock().unwrap().record)
+    }
+}
+
+impl SendTimesimpClient for ClientSimp {
+    async fn store_offset(&mut self, offset: SignedDuration) -> Result<(), Self::Err> {
+        self.storage.lock().unwrap().offset = Some(offset);
+        Ok(())
+    }
+
+    async fn query_server(
+        &self,
+        request: timesimp::Request,
+    ) -> Result<timesimp::Response, Self::Err> {
+        spawn_answer(self.server.clone(), request).await.unwrap()
+    }
+
+    async fn sleep(&self, duration: Duration) {
+        tokio::time::sleep(duration).await;
+    }
+
+    async fn store_sync_record(&mut self, record: SyncRecord) -> Result<(), Self::Err> {
+        self.storage.lock().unwrap().record = Some(record);
+        Ok(())
+    }
+}
+
+/// Generic code can only spawn the server endpoint if its future is known to be `Send`.
+fn spawn_answer<T: SendTimesimpServer + 'static>(
+    server: Arc<T>,
+    request: timesimp::Request,
+) -> tokio::task::JoinHandle<Result<timesimp::Response, T::Err>>
+where
+    T::Err: 'static,
+{
+    tokio::spawn(async move { server.answer_client_send(request).await })
+}
+
+/// Generic code can only spawn the sync if its future is known to be `Send`.
+fn spawn_sync<T: SendTimesimpClient + 'static>(
+    mut client: T,
+) -> tokio::task::JoinHandle<SignedDuration> {
+    tokio::spawn(async move {
+        client
+            .attempt_sync_send(Settings {
+                jitter: Duration::from_millis(10),
+                ..Default::default()
+            })
+            .await
+            .unwrap()
+    })
+}
+
+/// Likewise for waiting on the adjusted time, from generic code.
+fn spawn_wait<T: SendTimesimpClient + 'static>(
+    client: T,
+    timestamp: Timestamp,
+) -> tokio::task::JoinHandle<Option<TimeBounds>> {
+    tokio::spawn(async move {
+        client.adjusted_timestamp_send().await.unwrap();
+        client
+            .wait_until_after_send(timestamp, 100_000)
+            .await
+            .unwrap()
+    })
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn spawned_sync() {
+    *SETUP;
+
+    let storage = Arc::new(Mutex::new(Storage::default()));
+    let client = ClientSimp {
+        storage: storage.clone(),
+        server: Arc::new(ServerSimp),
+    };
+
+    let offset = spawn_sync(client).await.unwrap() - SignedDuration::from_secs(5);
+    assert!(
+        offset > SignedDuration::from_millis(-5) && offset < SignedDuration::from_millis(5),
+        "offset - 5s = {offset:?}"
+    );
+
+    let client = ClientSimp {
+        storage: storage.clone(),
+        server: Arc::new(ServerSimp),
+    };
+    let bounds = spawn_wait(client, Timestamp::now()).await.unwrap();
+    assert!(bounds.is_some(), "the sync record is loaded");
+
+    let storage = storage.lock().unwrap();
+    assert_eq!(
+        storage.offset.unwrap(),
+        storage.record.unwrap().offset,
+        "sync record is stored through the blanket impl"
+    );
+}
+
+#[tokio::test]
+async fn all_traits_in_scope() {
+    *SETUP;
+
+    let mut client = ClientSimp {
+        storage: Default::default(),
+        server: Arc::new(ServerSimp),
+    };
+
+    // both the `Send` and the plain provided methods resolve without ambiguity
+    let offset = client
+        .attempt_sync_send(Settings {
+            jitter: Duration::from_millis(10),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+    let report = client
+        .attempt_sync_report(Settings {
+            jitter: Duration::from_millis(10),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+    assert!((report.offset - offset).abs() < SignedDuration::from_millis(5));
+    assert!(client.adjusted_timestamp().await.is_ok());
+}
@@ -0,0 +1,468 @@
+#![allow(missing_docs)]
+
+use std::{
+    sync::{
+        LazyLock, Mutex,
+        atomic::{AtomicUsize, Ordering},
+    },
+    time::{Duration, Instant},
+};
+
+use timesimp::{
+    Clock, History, Sample, Settings, SignedDuration, SyncError, TimeSource, TimesimpClient,
+    Timestamp,
+};
+
+static SETUP: LazyLock<()> = LazyLock::new(|| {
+    tracing_subscriber::fmt()
+        .with_max_level(tracing::Level::TRACE)
+        .init();
+});
+
+/// Simulated time, only advanced by the client's sleeps and queries.
+#[derive(Debug)]
+struct VirtualClock {
+    instant: Instant,
+    wall: Timestamp,
+    elapsed: Mutex<Duration>,
+    step: Mutex<SignedDuration>,
+}
+
+impl VirtualClock {
+    fn new() -> Self {
+        Self {
+            instant: Instant::now(),
+            wall: Timestamp::from_second(1_700_000_000).unwrap(),
+            elapsed: Mutex::new(Duration::ZERO),
+            step: Mutex::new(SignedDuration::ZERO),
+        }
+    }
+
+    /// Step the wall clock, without affecting the monotonic clock.
+    fn step(&self, by: SignedDuration) {
+        *self.step.lock().unwrap() += by;
+    }
+
+    fn advance(&self, duration: Duration) {
+        *self.elapsed.lock().unwrap() += duration;
+    }
+
+    fn elapsed(&self) -> Duration {
+        *self.elapsed.lock().unwrap()
+    }
+}
+
+impl Clock for VirtualClock {
+    fn now(&self) -> Timestamp {
+        self.wall + self.elapsed() + *self.step.lock().unwrap()
+    }
+
+    fn instant(&self) -> Instant {
+        self.instant + self.elapsed()
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+#[error("Test error")]
+struct TestError;
+
+/// A client of a simulated server, ahead of it by `server_offset`.
+#[derive(Debug)]
+struct SimClient {
+    offset: Option<SignedDuration>,
+    clock: VirtualClock,
+    server_offset: SignedDuration,
+    round_trip: Duration,
+    step_backwards_at: Option<Timestamp>,
+    step_during_query: SignedDuration,
+    samples: Vec<Sample>,
+}
+
+impl SimClient {
+    fn new(server_offset: SignedDuration) -> Self {
+        Self {
+            offset: None,
+            clock: VirtualClock::new(),
+            server_offset,
+            round_trip: Duration::from_millis(80),
+            step_backwards_at: None,
+            step_during_query: SignedDuration::ZERO,
+            samples: Vec::new(),
+        }
+    }
+}
+
+impl TimeSource for SimClient {
+    type Err = TestError;
+
+    async fn load_offset(&self) -> Result<Option<SignedDuration>, Self::Err> {
+        Ok(self.offset)
+    }
+
+    fn clock(&self) -> &dyn Clock {
+        &self.clock
+    }
+}
+
+impl TimesimpClient for SimClient {
+    async fn store_offset(&mut self, offset: SignedDuration) -> Result<(), Self::Err> {
+        self.offset = Some(offset);
+        Ok(())
+    }
+
+    async fn query_server(
+        &self,
+        request: timesimp::Request,
+    ) -> Result<timesimp::Response, Self::Err> {
+        self.clock.advance(self.round_trip / 2);
+        let server = self.clock.now() + self.server_offset;
+        self.clock.step(self.step_during_query);
+        self.clock.advance(self.round_trip / 2);
+        if self
+            .step_backwards_at
+            .is_some_and(|at| request.client > at && self.clock.now() > request.client)
+        {
+            self.clock.step(SignedDuration::from_mins(-1));
+        }
+        Ok(timesimp::Response {
+            client: request.client,
+            server,
+        })
+    }
+
+    async fn sleep(&self, duration: Duration) {
+        self.clock.advance(duration);
+    }
+
+    async fn store_samples(&mut self, samples: Vec<Sample>) -> Result<(), Self::Err> {
+        self.samples = samples;
+        Ok(())
+    }
+}
+
+/// Tokio's clock, which is simulated when the runtime is paused.
+///
+/// Unlike the [`VirtualClock`], time passes for all tasks at once, so queries can overlap.
+#[derive(Debug)]
+struct TokioClock {
+    started: tokio::time::Instant,
+    wall: Timestamp,
+}
+
+impl TokioClock {
+    fn new() -> Self {
+        Self {
+            started: tokio::time::Instant::now(),
+            wall: Timestamp::from_second(1_700_000_000).unwrap(),
+        }
+    }
+
+    fn elapsed(&self) -> Duration {
+        self.started.elapsed()
+    }
+}
+
+impl Clock for TokioClock {
+    fn now(&self) -> Timestamp {
+        self.wall + self.elapsed()
+    }
+
+    fn instant(&self) -> Instant {
+        tokio::time::Instant::now().into_std()
+    }
+}
+
+/// A client of a simulated server over a slow link, in tokio's paused time.
+#[derive(Debug)]
+struct LinkClient {
+    offset: Option<SignedDuration>,
+    clock: TokioClock,
+    server_offset: SignedDuration,
+    round_trip: Duration,
+    late_timer: bool,
+    queries: AtomicUsize,
+    in_flight: AtomicUsize,
+    max_in_flight: AtomicUsize,
+}
+
+impl LinkClient {
+    fn new(server_offset: SignedDuration, round_trip: Duration) -> Self {
+        Self {
+            offset: None,
+            clock: TokioClock::new(),
+            server_offset,
+            round_trip,
+            late_timer: false,
+            queries: AtomicUsize::new(0),
+            in_flight: AtomicUsize::new(0),
+            max_in_flight: AtomicUsize::new(0),
+        }
+    }
+}
+
+/// Counts a query as in flight until it's dropped, whether it completed or was abandoned.
+struct InFlight<'a>(&'a AtomicUsize);
+
+impl Drop for InFlight<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+impl TimeSource for LinkClient {
+    type Err = TestError;
+
+    async fn load_offset(&self) -> Result<Option<SignedDuration>, Self::Err> {
+        Ok(self.offset)
+    }
+
+    fn clock(&self) -> &dyn Clock {
+        &self.clock
+    }
+}
+
+impl TimesimpClient for LinkClient {
+    async fn store_offset(&mut self, offset: SignedDuration) -> Result<(), Self::Err> {
+        self.offset = Some(offset);
+        Ok(())
+    }
+
+    async fn query_server(
+        &self,
+        request: timesimp::Request,
+    ) -> Result<timesimp::Response, Self::Err> {
+        self.queries.fetch_add(1, Ordering::Relaxed);
+        let in_flight = self.in_flight.fetch_add(1, Ordering::Relaxed) + 1;
+        let _in_flight = InFlight(&self.in_flight);
+        self.max_in_flight.fetch_max(in_flight, Ordering::Relaxed);
+
+        tokio::time::sleep(self.round_trip / 2).await;
+        let server = self.clock.now() + self.server_offset;
+        tokio::time::sleep(self.round_trip / 2).await;
+        Ok(timesimp::Response {
+            client: request.client,
+            server,
+        })
+    }
+
+    async fn sleep(&self, duration: Duration) {
+        if self.late_timer {
+            // like a busy or coarse timer, the longer the sleep the later it wakes
+            tokio::time::sleep(duration.mul_f64(1.5)).await;
+        } else {
+            tokio::time::sleep(duration).await;
+        }
+    }
+}
+
+#[tokio::test]
+async fn exact_in_virtual_time() {
+    *SETUP;
+
+    let mut client = SimClient::new(SignedDuration::from_secs(-3));
+    let started = Instant::now();
+
+    let report = client
+        .attempt_sync_report(Settings {
+            samples: 15,
+            jitter: Duration::from_secs(10),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+    assert_eq!(report.offset, SignedDuration::from_secs(-3));
+    assert_eq!(report.samples, 15);
+
+    // the gaps and round trips happen in virtual time only
+    assert!(client.clock.elapsed() >= Duration::from_millis(80 * 15));
+    assert!(started.elapsed() < Duration::from_secs(1));
+}
+
+#[tokio::test]
+async fn deadline_in_virtual_time() {
+    *SETUP;
+
+    let mut client = SimClient::new(SignedDuration::ZERO);
+
+    let report = client
+        .attempt_sync_report(Settings {
+            samples: 255,
+            jitter: Duration::from_secs(10),
+            deadline: Some(Duration::from_secs(30)),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+    assert!(report.deadline_reached, "{report:?}");
+    assert!(report.samples < 255, "{report:?}");
+    assert!(
+        client.clock.elapsed() < Duration::from_secs(30),
+        "elapsed = {:?}",
+        client.clock.elapsed()
+    );
+}
+
+#[tokio::test]
+async fn clock_went_backwards() {
+    *SETUP;
+
+    let mut client = SimClient::new(SignedDuration::ZERO);
+    client.step_backwards_at = Some(client.clock.now() + SignedDuration::from_secs(5));
+
+    let err = client
+        .attempt_sync_report(Settings {
+            jitter: Duration::from_secs(2),
+            samples: 15,
+            ..Default::default()
+        })
+        .await
+        .unwrap_err();
+    assert!(
+        matches!(err, SyncError::ClockWentBackwards { by } if by > Duration::from_secs(50)),
+        "{err:?}"
+    );
+}
+
+#[tokio::test]
+async fn clock_stepped_during_query() {
+    *SETUP;
+
+    let mut client = SimClient::new(SignedDuration::from_secs(-3));
+    client.step_during_query = SignedDuration::from_mins(1);
+
+    let report = client
+        .attempt_sync_report(Settings {
+            samples: 5,
+            jitter: Duration::from_secs(2),
+            history: Some(History {
+                window: Duration::from_secs(3600),
+                max_samples: 10,
+            }),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+
+    // the round trips are measured on the monotonic clock, so the steps don't count in them
+    assert_eq!(report.offset, SignedDuration::from_secs(-3));
+    assert_eq!(client.samples.len(), 5);
+    for sample in &client.samples {
+        assert_eq!(sample.latency, Duration::from_millis(40), "{sample:?}");
+    }
+}
+
+#[tokio::test(start_paused = true)]
+async fn abandoned_queries_are_not_in_flight() {
+    *SETUP;
+
+    // the session abandons each query before the query's own timeout wakes up
+    let mut client = LinkClient::new(SignedDuration::ZERO, Duration::from_secs(10));
+    client.late_timer = true;
+
+    let err = client
+        .attempt_sync_report(Settings {
+            samples: 5,
+            jitter: Duration::from_millis(10),
+            timeout: Some(Duration::from_millis(100)),
+            in_flight: 2,
+            ..Default::default()
+        })
+        .await
+        .unwrap_err();
+    assert!(
+        matches!(&err, SyncError::AllQueriesFailed { errors, timeouts: 5 } if errors.is_empty()),
+        "{err:?}"
+    );
+    assert_eq!(client.queries.load(Ordering::Relaxed), 5);
+    assert_eq!(client.max_in_flight.load(Ordering::Relaxed), 2);
+    assert_eq!(client.in_flight.load(Ordering::Relaxed), 0);
+}
+
+#[tokio::test(start_paused = true)]
+async fn pipelined_high_latency() {
+    *SETUP;
+
+    let mut client = LinkClient::new(SignedDuration::from_secs(5), Duration::from_millis(600));
+
+    let report = client
+        .attempt_sync_report(Settings {
+            jitter: Duration::from_millis(100),
+            in_flight: 5,
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+    assert_eq!(report.offset, SignedDuration::from_secs(5));
+    assert_eq!(report.samples, 5);
+    assert_eq!(client.max_in_flight.load(Ordering::Relaxed), 5);
+
+    // one after the other, the round trips alone would take 3s
+    assert!(
+        client.clock.elapsed() < Duration::from_millis(1500),
+        "elapsed = {:?}",
+        client.clock.elapsed()
+    );
+}
+
+#[tokio::test(start_paused = true)]
+async fn deadline_without_enough_samples() {
+    *SETUP;
+
+    let mut client = LinkClient::new(SignedDuration::ZERO, Duration::from_millis(200));
+
+    let err = client
+        .attempt_sync_report(Settings {
+            samples: 5,
+            jitter: Duration::from_millis(10),
+            deadline: Some(Duration::from_millis(300)),
+            ..Default::default()
+        })
+        .await
+        .unwrap_err();
+    assert!(
+        matches!(
+            err,
+            SyncError::TooFewInliers {
+                samples: 1,
+                timeouts: 0,
+                deadline_reached: true,
+                ..
+            }
+        ),
+        "{err:?}"
+    );
+    assert_eq!(client.clock.elapsed(), Duration::from_millis(300));
+}
+
+#[tokio::test(start_paused = true)]
+async fn pipelined_deadline_abandons_in_flight() {
+    *SETUP;
+
+    let mut client = LinkClient::new(SignedDuration::ZERO, Duration::from_millis(500));
+
+    let err = client
+        .attempt_sync_report(Settings {
+            samples: 5,
+            jitter: Duration::from_millis(10),
+            deadline: Some(Duration::from_millis(200)),
+            in_flight: 5,
+            ..Default::default()
+        })
+        .await
+        .unwrap_err();
+    assert!(
+        matches!(
+            &err,
+            SyncError::TooFewInliers {
+                samples: 0,
+                errors,
+                timeouts: 0,
+                deadline_reached: true,
+            } if errors.is_empty()
+        ),
+        "{err:?}"
+    );
+    assert_eq!(client.queries.load(Ordering::Relaxed), 5);
+    assert_eq!(client.in_flight.load(Ordering::Relaxed), 0);
+    assert_eq!(client.clock.elapsed(), Duration::from_millis(200));
+}
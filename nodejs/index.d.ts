@@ -9,6 +9,52 @@ export interface Settings {
   samples?: number
   /** The maximum amount of time in microseconds between taking two samples. */
   jitter?: number
+  /**
+   * How many microseconds longer the outbound leg (to the server) takes than the return leg.
+   *
+   * Use this to compensate for a known path asymmetry. Negative if the outbound leg is shorter.
+   */
+  asymmetryBias?: number
+  /**
+   * How outliers are eliminated from the samples: `"stddev"` (the default), `"mad"`, or `"iqr"`.
+   *
+   * The latter two are robust to spikes.
+   */
+  outliers?: string
+  /**
+   * The maximum amount of time in microseconds to wait for a single `query()`.
+   *
+   * Queries that take longer are abandoned and counted as failures.
+   */
+  timeout?: number
+  /**
+   * The maximum amount of time in microseconds for the whole synchronisation attempt.
+   *
+   * When it runs out, the attempt finishes with the samples it has, if they're enough.
+   */
+  deadline?: number
+  /**
+   * How many `query()` calls may be in flight at once.
+   *
+   * By default, queries are sent one after the other. Over high-latency links, allowing
+   * several in flight makes synchronisation much faster.
+   */
+  inFlight?: number
+  /**
+   * The largest offset in microseconds that may be stored, in either direction.
+   *
+   * If the calculated offset is larger, it's not stored, and `attemptSync()` throws.
+   */
+  maxOffset?: number
+  /**
+   * The largest change in microseconds from the current offset that may be stored.
+   *
+   * If the calculated offset is further from the stored one, it's not stored, and
+   * `attemptSync()` throws.
+   */
+  maxChange?: number
+  /** Whether the first synchronisation may set any offset, regardless of `maxOffset`. */
+  firstSyncMayStep?: boolean
 }
 /**
  * Simple sans-io timesync client and server.
@@ -25,10 +71,14 @@ export interface Settings {
  * This library provides a sans-io implementation: you bring in your transport and your storage;
  * timesimp gives you time offsets. Internally, timesimp is implemented in Rust.
  *
- * If the local clock goes backward during a synchronisation, the invalid delta is discarded; this
- * may cause the sync attempt to fail, especially if the `samples` count is lowered to its minimum
- * of 3. This is a deliberate design decision: you should handle failure and retry, and the sync
- * will proceed correctly when the clock is stable.
+ * Round trips are timed on the monotonic clock, so if the local clock is stepped during a
+ * synchronisation, the latency of the sample in flight is not corrupted. However, deltas obtained
+ * before and after the step are relative to different clocks. If the clock steps backwards, this
+ * is noticed from the request timestamps, and the attempt is abandoned with `attemptSync()`
+ * returning `null`. A step forwards looks the same as time passing between samples, so it isn't
+ * detected: the outlier elimination may discard some deltas, or the resulting offset may be off
+ * until the next sync. This is a deliberate design decision: you should sync regularly, and the
+ * sync will proceed correctly when the clock is stable.
  *
  * [paper]: https://web.archive.org/web/20160310125700/http://mine-control.com/zack/timesync/timesync.html
  */
@@ -100,12 +150,15 @@ export declare class Timesimp {
    *
    * If `load()` returns `null`, this method will attempt to `store()` the first delta it gets
    * from the server. This lets you get an “accurate enough” timestamp pretty quickly, instead
-   * of waiting for a full round of samples. Errors from that store are ignored silently.
+   * of waiting for a full round of samples. Errors from that store are thrown.
    *
    * If this returns `null`, not enough samples were obtained to have enough confidence in the
-   * result, likely because the `query()` function encountered an error for most tries. Errors
-   * from `query()` are not returned; you may want to catch them for logging before passing them
-   * on.
+   * result, likely because the `query()` function encountered an error for most tries, or the
+   * clock went backwards during the attempt. Errors from `query()` are not returned; you may
+   * want to catch them for logging before passing them on.
+   *
+   * If the calculated offset is outside the `maxOffset` or `maxChange` limits, it's not stored,
+   * and this throws.
    *
    * On success, returns the calculated offset in microseconds.
    */
@@ -5,7 +5,9 @@ use napi::{
     threadsafe_function::{ThreadsafeFunction},
 };
 use napi_derive::*;
-use timesimp::{Request, Response, SignedDuration, Timesimp as _};
+use timesimp::{
+    Request, Response, SignedDuration, TimeSource as _, TimesimpClient as _, TimesimpServer as _,
+};
 use tokio::sync::Mutex;
 
 /// Simple sans-io timesync client and server.
@@ -22,10 +24,14 @@ use tokio::sync::Mutex;
 /// This library provides a sans-io implementation: you bring in your transport and your storage;
 /// timesimp gives you time offsets. Internally, timesimp is implemented in Rust.
 ///
-/// If the local clock goes backward during a synchronisation, the invalid delta is discarded; this
-/// may cause the sync attempt to fail, especially if the `samples` count is lowered to its minimum
-/// of 3. This is a deliberate design decision: you should handle failure and retry, and the sync
-/// will proceed correctly when the clock is stable.
+/// Round trips are timed on the monotonic clock, so if the local clock is stepped during a
+/// synchronisation, the latency of the sample in flight is not corrupted. However, deltas obtained
+/// before and after the step are relative to different clocks. If the clock steps backwards, this
+/// is noticed from the request timestamps, and the attempt is abandoned with `attemptSync()`
+/// returning `null`. A step forwards looks the same as time passing between samples, so it isn't
+/// detected: the outlier elimination may discard some deltas, or the resulting offset may be off
+/// until the next sync. This is a deliberate design decision: you should sync regularly, and the
+/// sync will proceed correctly when the clock is stable.
 ///
 /// [paper]: https://web.archive.org/web/20160310125700/http://mine-control.com/zack/timesync/timesync.html
 #[napi]
@@ -58,7 +64,7 @@ fn add_context(ctx: &'static str, line: u32) -> impl Fn(Error) -> Error {
     }
 }
 
-impl timesimp::Timesimp for TimesimpImpl {
+impl timesimp::TimeSource for TimesimpImpl {
     type Err = Error<Status>;
 
     async fn load_offset(&self) -> Result<Option<SignedDuration>> {
@@ -71,7 +77,11 @@ impl timesimp::Timesimp for TimesimpImpl {
             .map_err(add_context("load_offset", line!()))?
             .map(SignedDuration::from_micros))
     }
+}
 
+impl timesimp::TimesimpServer for TimesimpImpl {}
+
+impl timesimp::TimesimpClient for TimesimpImpl {
     async fn store_offset(&mut self, offset: SignedDuration) -> Result<()> {
         self.store
             .call_async(Ok((offset.as_micros() as i64,)))
@@ -95,7 +105,7 @@ impl timesimp::Timesimp for TimesimpImpl {
             .map_err(add_context("query_server", line!()))
     }
 
-    async fn sleep(duration: Duration) {
+    async fn sleep(&self, duration: Duration) {
         tokio::time::sleep(duration).await
     }
 }
@@ -198,37 +208,87 @@ impl Timesimp {
     ///
     /// If `load()` returns `null`, this method will attempt to `store()` the first delta it gets
     /// from the server. This lets you get an “accurate enough” timestamp pretty quickly, instead
-    /// of waiting for a full round of samples. Errors from that store are ignored silently.
+    /// of waiting for a full round of samples. Errors from that store are thrown.
     ///
     /// If this returns `null`, not enough samples were obtained to have enough confidence in the
-    /// result, likely because the `query()` function encountered an error for most tries. Errors
-    /// from `query()` are not returned; you may want to catch them for logging before passing them
-    /// on.
+    /// result, likely because the `query()` function encountered an error for most tries, or the
+    /// clock went backwards during the attempt. Errors from `query()` are not returned; you may
+    /// want to catch them for logging before passing them on.
+    ///
+    /// If the calculated offset is outside the `maxOffset` or `maxChange` limits, it's not stored,
+    /// and this throws.
     ///
     /// On success, returns the calculated offset in microseconds.
     #[napi]
     pub async fn attempt_sync(&self, settings: Settings) -> Result<Option<i64>> {
         let defaults = timesimp::Settings::default();
+        let outliers = match settings.outliers.as_deref() {
+            None => defaults.outliers,
+            Some("stddev") => timesimp::OutlierFilter::StdDev,
+            Some("mad") => timesimp::OutlierFilter::Mad,
+            Some("iqr") => timesimp::OutlierFilter::Iqr,
+            Some(other) => {
+                return Err(Error::new(
+                    Status::InvalidArg,
+                    format!("unknown outlier filter: {other}"),
+                ))
+                .map_err(add_context("attempt_sync", line!()));
+            }
+        };
         let settings = timesimp::Settings {
             samples: settings.samples.unwrap_or(defaults.samples),
             jitter: settings
                 .jitter
                 .map(|j| Duration::from_micros(j as _))
                 .unwrap_or(defaults.jitter),
+            asymmetry: settings
+                .asymmetry_bias
+                .map(|b| timesimp::Asymmetry::Bias(SignedDuration::from_micros(b)))
+                .unwrap_or(defaults.asymmetry),
+            outliers,
+            timeout: settings
+                .timeout
+                .map(|t| Duration::from_micros(t as _))
+                .or(defaults.timeout),
+            deadline: settings
+                .deadline
+                .map(|d| Duration::from_micros(d as _))
+                .or(defaults.deadline),
+            in_flight: settings.in_flight.unwrap_or(defaults.in_flight),
+            max_offset: settings
+                .max_offset
+                .map(|m| Duration::from_micros(m.unsigned_abs()))
+                .or(defaults.max_offset),
+            max_change: settings
+                .max_change
+                .map(|m| Duration::from_micros(m.unsigned_abs()))
+                .or(defaults.max_change),
+            first_sync_may_step: settings
+                .first_sync_may_step
+                .unwrap_or(defaults.first_sync_may_step),
+            ..defaults
+        };
+        let res = match self.0.lock().await.attempt_sync(settings).await {
+            Ok(offset) => Some(offset),
+            Err(
+                timesimp::SyncError::AllQueriesFailed { .. }
+                | timesimp::SyncError::ClockWentBackwards { .. }
+                | timesimp::SyncError::TooFewInliers { .. },
+            ) => None,
+            Err(timesimp::SyncError::Storage(err)) => {
+                return Err(err).map_err(add_context("attempt_sync", line!()));
+            }
+            Err(err) => {
+                return Err(Error::new(Status::GenericFailure, err))
+                    .map_err(add_context("attempt_sync", line!()));
+            }
         };
-        let res = self
-            .0
-            .lock()
-            .await
-            .attempt_sync(settings)
-            .await
-            .map_err(add_context("attempt_sync", line!()))?;
         Ok(res.map(|offset| offset.as_micros() as _))
     }
 }
 
 /// Settings for a synchronisation attempt.
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone)]
 #[napi(object)]
 pub struct Settings {
     /// How many samples to gather for synchronisation.
@@ -236,4 +296,44 @@ pub struct Settings {
 
     /// The maximum amount of time in microseconds between taking two samples.
     pub jitter: Option<u32>,
+
+    /// How many microseconds longer the outbound leg (to the server) takes than the return leg.
+    ///
+    /// Use this to compensate for a known path asymmetry. Negative if the outbound leg is shorter.
+    pub asymmetry_bias: Option<i64>,
+
+    /// How outliers are eliminated from the samples: `"stddev"` (the default), `"mad"`, or `"iqr"`.
+    ///
+    /// The latter two are robust to spikes.
+    pub outliers: Option<String>,
+
+    /// The maximum amount of time in microseconds to wait for a single `query()`.
+    ///
+    /// Queries that take longer are abandoned and counted as failures.
+    pub timeout: Option<u32>,
+
+    /// The maximum amount of time in microseconds for the whole synchronisation attempt.
+    ///
+    /// When it runs out, the attempt finishes with the samples it has, if they're enough.
+    pub deadline: Option<u32>,
+
+    /// How many `query()` calls may be in flight at once.
+    ///
+    /// By default, queries are sent one after the other. Over high-latency links, allowing
+    /// several in flight makes synchronisation much faster.
+    pub in_flight: Option<u8>,
+
+    /// The largest offset in microseconds that may be stored, in either direction.
+    ///
+    /// If the calculated offset is larger, it's not stored, and `attemptSync()` throws.
+    pub max_offset: Option<i64>,
+
+    /// The largest change in microseconds from the current offset that may be stored.
+    ///
+    /// If the calculated offset is further from the stored one, it's not stored, and
+    /// `attemptSync()` throws.
+    pub max_change: Option<i64>,
+
+    /// Whether the first synchronisation may set any offset, regardless of `maxOffset`.
+    pub first_sync_may_step: Option<bool>,
 }